@@ -0,0 +1,475 @@
+// Bulk ticket creation from a CSV. Every row is validated with the same rules as
+// `create_ticket` before anything is sent, rows are batched into transactions, and a
+// results file records which ticket each row became.
+//
+// Rows are written to the results file as `pending`, with the batch's signature and the last
+// block height its blockhash is valid for, before their transaction is sent, and as `created`
+// once it confirms. On a re-run, pending rows whose ticket exists on-chain are marked created.
+// The rest are planned again only once their transaction is known to have failed or can no
+// longer land, so nothing is created twice.
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use vestor_client::instructions::{NewTicket, TicketTerms};
+use vestor_client::schedule::{validate_terms, ScheduleError};
+use vestor_client::state;
+
+use crate::units::{to_base_units, to_ui_amount};
+use crate::{new_ticket_instructions, Ctx};
+
+
+const COLUMNS: [&str; 5] = ["beneficiary", "amount", "cliff", "vesting", "irrevocable"];
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    // Line in the CSV, used as the row's id in the results file
+    pub line: u64,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub irrevocable: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Pending,
+    Created,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub line: u64,
+    pub beneficiary: Pubkey,
+    pub ticket: Pubkey,
+    pub status: Status,
+    // The batch transaction that creates the ticket
+    pub signature: String,
+    // Last block height at which that transaction can land
+    pub last_valid_block_height: u64,
+}
+
+
+// Parses and validates every row, reporting all invalid rows at once
+pub fn parse_rows<R: Read>(reader: R, decimals: u8) -> Result<Vec<Row>> {
+    let mut csv = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+
+    let headers: Vec<String> = csv.headers()?.iter().map(|h| h.to_lowercase()).collect();
+    if headers != COLUMNS {
+        bail!("expected columns {}, found {}", COLUMNS.join(","), headers.join(","));
+    }
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for record in csv.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        match parse_row(&record, line, decimals) {
+            Ok(row) => rows.push(row),
+            Err(e) => errors.push(format!("line {}: {}", line, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("{} invalid row(s):\n{}", errors.len(), errors.join("\n"));
+    }
+    Ok(rows)
+}
+
+fn parse_row(record: &csv::StringRecord, line: u64, decimals: u8) -> Result<Row> {
+    let field = |i: usize| record.get(i).ok_or_else(|| anyhow!("missing {}", COLUMNS[i]));
+
+    let beneficiary = field(0)?.parse().map_err(|_| anyhow!("invalid beneficiary {:?}", field(0).unwrap_or("")))?;
+    let amount = to_base_units(field(1)?, decimals)?;
+    let cliff = field(2)?.parse().map_err(|_| anyhow!("invalid cliff {:?}", field(2).unwrap_or("")))?;
+    let vesting = field(3)?.parse().map_err(|_| anyhow!("invalid vesting {:?}", field(3).unwrap_or("")))?;
+    let irrevocable = parse_bool(field(4)?)?;
+
+    validate_terms(amount, cliff, vesting).map_err(|e| anyhow!(describe(e)))?;
+
+    Ok(Row { line, beneficiary, amount, cliff, vesting, irrevocable })
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" | "" => Ok(false),
+        _ => bail!("invalid irrevocable flag {:?}", value),
+    }
+}
+
+// Same wording as the program's errors for these rules
+fn describe(e: ScheduleError) -> &'static str {
+    match e {
+        ScheduleError::ZeroAmount => "Amount must be greater than zero.",
+        ScheduleError::VestingShorterThanCliff => "Vesting period should be equal or longer to the cliff",
+        ScheduleError::ZeroVesting => "Vesting period must be at least one day",
+        _ => "invalid ticket terms",
+    }
+}
+
+
+// A missing results file means nothing has been created yet
+pub fn load_results(path: &Path) -> Result<BTreeMap<u64, Outcome>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut csv = csv::Reader::from_path(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut results = BTreeMap::new();
+    for record in csv.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        let outcome = Outcome {
+            line: field(0).parse()?,
+            beneficiary: field(1).parse().map_err(|_| anyhow!("invalid beneficiary in {}", path.display()))?,
+            ticket: field(2).parse().map_err(|_| anyhow!("invalid ticket in {}", path.display()))?,
+            status: match field(3) {
+                "created" => Status::Created,
+                "pending" => Status::Pending,
+                other => bail!("unknown status {:?} in {}", other, path.display()),
+            },
+            signature: field(4).to_string(),
+            last_valid_block_height: field(5).parse().map_err(|_| anyhow!("invalid block height in {}", path.display()))?,
+        };
+        results.insert(outcome.line, outcome);
+    }
+    Ok(results)
+}
+
+// Written to a temporary file first so an interrupted write never loses earlier results
+pub fn save_results(path: &Path, results: &BTreeMap<u64, Outcome>) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut csv = csv::Writer::from_path(&tmp)?;
+        csv.write_record(&["row", "beneficiary", "ticket", "status", "signature", "last_valid_block_height"])?;
+        for outcome in results.values() {
+            let status = match outcome.status {
+                Status::Created => "created",
+                Status::Pending => "pending",
+            };
+            csv.write_record(&[
+                outcome.line.to_string(),
+                outcome.beneficiary.to_string(),
+                outcome.ticket.to_string(),
+                status.to_string(),
+                outcome.signature.clone(),
+                outcome.last_valid_block_height.to_string(),
+            ])?;
+        }
+        csv.flush()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+
+// Settles pending rows left by an earlier run: created if their ticket exists, forgotten once
+// their transaction failed or expired. Bails while a transaction may still land.
+fn reconcile(ctx: &Ctx, rows: &[Row], results: &mut BTreeMap<u64, Outcome>) -> Result<()> {
+    for row in rows {
+        if let Some(outcome) = results.get(&row.line) {
+            if outcome.beneficiary != row.beneficiary {
+                bail!("line {} of the CSV does not match the results file", row.line);
+            }
+        }
+    }
+
+    let pending: Vec<Outcome> = results.values().filter(|o| o.status == Status::Pending).cloned().collect();
+    for outcome in pending {
+        let account = ctx.rpc.get_account_with_commitment(&outcome.ticket, ctx.rpc.commitment())?.value;
+        let created = account
+            .and_then(|a| state::ticket(&a.data).ok())
+            .map_or(false, |ticket| ticket.claimant == outcome.beneficiary);
+
+        if created {
+            println!("line {}: found ticket {} from an earlier run", outcome.line, outcome.ticket);
+            results.get_mut(&outcome.line).unwrap().status = Status::Created;
+            continue;
+        }
+
+        let signature: Signature = outcome
+            .signature
+            .parse()
+            .map_err(|_| anyhow!("line {}: invalid signature {:?} in the results file", outcome.line, outcome.signature))?;
+        let status = ctx.rpc.get_signature_statuses_with_history(&[signature])?.value.pop().flatten();
+        match status {
+            Some(status) if status.err.is_some() => {
+                println!("line {}: transaction {} failed, planning the row again", outcome.line, signature);
+                results.remove(&outcome.line);
+            }
+            Some(_) => bail!(
+                "line {}: transaction {} has landed but ticket {} is not visible yet; re-run later",
+                outcome.line,
+                signature,
+                outcome.ticket,
+            ),
+            None if ctx.rpc.get_block_height()? > outcome.last_valid_block_height => {
+                results.remove(&outcome.line);
+            }
+            None => bail!(
+                "line {}: transaction {} may still land until block {}; re-run later",
+                outcome.line,
+                signature,
+                outcome.last_valid_block_height,
+            ),
+        }
+    }
+    Ok(())
+}
+
+
+pub fn run(ctx: &Ctx, vestor_key: Pubkey, deposit: Pubkey, csv_path: &str, results_path: &str, batch_size: usize) -> Result<()> {
+    if batch_size == 0 {
+        bail!("--batch-size must be at least 1");
+    }
+    let payer = ctx.payer()?;
+    let results_path = Path::new(results_path);
+
+    let deposit_account = ctx.token_account(&deposit)?;
+    let mint = deposit_account.mint;
+    let decimals = ctx.mint_decimals(&mint)?;
+
+    let rows = parse_rows(File::open(csv_path).with_context(|| format!("opening {}", csv_path))?, decimals)?;
+
+    let mut results = load_results(results_path)?;
+    reconcile(ctx, &rows, &mut results)?;
+    if !ctx.dry_run {
+        save_results(results_path, &results)?;
+    }
+
+    let todo: Vec<&Row> = rows
+        .iter()
+        .filter(|row| !matches!(results.get(&row.line), Some(o) if o.status == Status::Created))
+        .collect();
+    if todo.is_empty() {
+        println!("All {} rows have been created", rows.len());
+        return Ok(());
+    }
+
+    // The remaining checks `create_ticket` makes against chain state, for the whole plan
+    let total: u128 = todo.iter().map(|row| row.amount as u128).sum();
+    if total > deposit_account.amount as u128 {
+        bail!(
+            "Ask Admin/Owner to mint more tokens: the remaining rows need {} but the deposit holds {}",
+            to_ui_amount(total.min(u64::MAX as u128) as u64, decimals),
+            to_ui_amount(deposit_account.amount, decimals),
+        );
+    }
+    let vestor = ctx.vestor(&vestor_key)?;
+    if vestor.tickets_issued() as usize + todo.len() > u8::MAX as usize {
+        bail!(
+            "the vestor has issued {} tickets and can issue at most {}, {} rows remain",
+            vestor.tickets_issued(),
+            u8::MAX,
+            todo.len(),
+        );
+    }
+
+    println!("Creating {} of {} tickets in batches of {}", todo.len(), rows.len(), batch_size);
+
+    let mut next_seed = vestor.tickets_issued();
+    let mut known_vaults = HashSet::new();
+    for batch in todo.chunks(batch_size) {
+        // Another creator may have used seeds since the last batch
+        if !ctx.dry_run {
+            next_seed = ctx.vestor(&vestor_key)?.tickets_issued();
+        }
+
+        let mut ixs = Vec::new();
+        let mut keypairs = Vec::new();
+        for (i, row) in batch.iter().enumerate() {
+            let claimant_vault = get_associated_token_address(&row.beneficiary, &mint);
+            if known_vaults.insert(claimant_vault)
+                && ctx.rpc.get_account_with_commitment(&claimant_vault, ctx.rpc.commitment())?.value.is_none()
+            {
+                ixs.push(create_associated_token_account(&payer.pubkey(), &row.beneficiary, &mint));
+            }
+
+            let ticket = Keypair::new();
+            let vault = Keypair::new();
+            ixs.extend(new_ticket_instructions(
+                ctx,
+                &payer.pubkey(),
+                &vestor_key,
+                next_seed + i as u8,
+                &NewTicket {
+                    ticket: ticket.pubkey(),
+                    owner: payer.pubkey(),
+                    token_mint: mint,
+                    ticket_creator_deposit_token_vault: deposit,
+                    claimant_receive_token_vault: claimant_vault,
+                    vault: vault.pubkey(),
+                },
+                &TicketTerms {
+                    beneficiary: row.beneficiary,
+                    cliff: row.cliff,
+                    vesting: row.vesting,
+                    amount: row.amount,
+                    irrevocable: row.irrevocable,
+                },
+            )?);
+            keypairs.push((row, ticket, vault));
+        }
+
+        let signers: Vec<&Keypair> = keypairs.iter().flat_map(|(_, ticket, vault)| vec![ticket, vault]).collect();
+        let (tx, last_valid_block_height) = ctx.sign(&ixs, &payer, &signers).with_context(|| {
+            format!("batch starting at line {} cannot be sent; lower --batch-size", batch[0].line)
+        })?;
+
+        for (row, ticket, _) in &keypairs {
+            results.insert(row.line, Outcome {
+                line: row.line,
+                beneficiary: row.beneficiary,
+                ticket: ticket.pubkey(),
+                status: Status::Pending,
+                signature: tx.signatures[0].to_string(),
+                last_valid_block_height,
+            });
+        }
+        if !ctx.dry_run {
+            save_results(results_path, &results)?;
+        }
+
+        let signature = ctx.submit(&tx).with_context(|| {
+            format!(
+                "batch starting at line {} failed; re-run with the same --results file to resume",
+                batch[0].line
+            )
+        })?;
+
+        for (row, ticket, _) in &keypairs {
+            println!("line {}: {} -> ticket {}", row.line, row.beneficiary, ticket.pubkey());
+            if signature.is_some() {
+                results.get_mut(&row.line).unwrap().status = Status::Created;
+            }
+        }
+        if !ctx.dry_run {
+            save_results(results_path, &results)?;
+        }
+
+        next_seed += batch.len() as u8;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::transaction::Transaction;
+
+    use crate::{check_size, ticket_instructions};
+
+    fn csv(rows: &str) -> String {
+        format!("beneficiary,amount,cliff,vesting,irrevocable\n{}", rows)
+    }
+
+    #[test]
+    fn parses_valid_rows() {
+        let beneficiary = Pubkey::new_unique();
+        let input = csv(&format!("{0}, 12.5, 30, 365, true\n{0},1,0,1,no\n", beneficiary));
+
+        let rows = parse_rows(input.as_bytes(), 6).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], Row { line: 2, beneficiary, amount: 12_500_000, cliff: 30, vesting: 365, irrevocable: true });
+        assert_eq!(rows[1].line, 3);
+        assert!(!rows[1].irrevocable);
+    }
+
+    #[test]
+    fn reports_every_invalid_row() {
+        let beneficiary = Pubkey::new_unique();
+        let input = csv(&format!(
+            "{0},0,0,10,false\n{0},5,30,10,false\nnot-a-key,5,0,10,false\n{0},5,0,10,maybe\n{0},5,0,0,false\n",
+            beneficiary
+        ));
+
+        let err = parse_rows(input.as_bytes(), 6).unwrap_err().to_string();
+        assert!(err.starts_with("5 invalid row(s)"));
+        assert!(err.contains("line 2: Amount must be greater than zero."));
+        assert!(err.contains("line 3: Vesting period should be equal or longer to the cliff"));
+        assert!(err.contains("line 4: invalid beneficiary"));
+        assert!(err.contains("line 5: invalid irrevocable flag"));
+        assert!(err.contains("line 6: Vesting period must be at least one day"));
+    }
+
+    // A batch as `run` builds it, with every claimant still needing a token account
+    fn batch_transaction(tickets: usize) -> Transaction {
+        let payer = Keypair::new();
+        let (vestor, mint, deposit) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut ixs = Vec::new();
+        let mut signers = vec![Keypair::from_bytes(&payer.to_bytes()).unwrap()];
+        for seed in 0..tickets {
+            let beneficiary = Pubkey::new_unique();
+            let claimant_vault = get_associated_token_address(&beneficiary, &mint);
+            let (ticket, vault) = (Keypair::new(), Keypair::new());
+            ixs.push(create_associated_token_account(&payer.pubkey(), &beneficiary, &mint));
+            ixs.extend(
+                ticket_instructions(
+                    &payer.pubkey(),
+                    &vestor,
+                    seed as u8,
+                    &NewTicket {
+                        ticket: ticket.pubkey(),
+                        owner: payer.pubkey(),
+                        token_mint: mint,
+                        ticket_creator_deposit_token_vault: deposit,
+                        claimant_receive_token_vault: claimant_vault,
+                        vault: vault.pubkey(),
+                    },
+                    &TicketTerms { beneficiary, cliff: 30, vesting: 365, amount: u64::MAX, irrevocable: true },
+                    u64::MAX,
+                    u64::MAX,
+                )
+                .unwrap(),
+            );
+            signers.extend([ticket, vault]);
+        }
+        let signers: Vec<&Keypair> = signers.iter().collect();
+        Transaction::new_signed_with_payer(&ixs, Some(&payer.pubkey()), &signers, Hash::default())
+    }
+
+    #[test]
+    fn only_one_ticket_fits_in_a_transaction() {
+        assert!(check_size(&batch_transaction(1)).is_ok());
+        assert!(check_size(&batch_transaction(2)).is_err());
+    }
+
+    #[test]
+    fn rejects_unexpected_columns() {
+        assert!(parse_rows("wallet,amount\n".as_bytes(), 6).is_err());
+    }
+
+    #[test]
+    fn results_round_trip() {
+        let path = std::env::temp_dir().join(format!("vestor-bulk-{}.csv", Pubkey::new_unique()));
+        let mut results = BTreeMap::new();
+        for (line, status) in [(2, Status::Created), (3, Status::Pending)] {
+            results.insert(line, Outcome {
+                line,
+                beneficiary: Pubkey::new_unique(),
+                ticket: Pubkey::new_unique(),
+                status,
+                signature: Signature::new_unique().to_string(),
+                last_valid_block_height: 1_000 + line,
+            });
+        }
+
+        save_results(&path, &results).unwrap();
+        assert_eq!(load_results(&path).unwrap(), results);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(load_results(&path).unwrap().is_empty());
+    }
+}
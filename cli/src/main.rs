@@ -0,0 +1,513 @@
+// `vestor`: operate the vestor program from a terminal. Amounts are read and printed in
+// whole tokens using the mint's decimals; `--dry-run` simulates instead of sending.
+mod bulk;
+mod units;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_sdk::account;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::sysvar;
+use solana_sdk::transaction::Transaction;
+
+use vestor_client::instructions::{self, NewTicket, TicketTerms, TICKET_SPACE};
+use vestor_client::{pda, state, Claimable, Ticket, USD_DECIMALS};
+
+use units::{to_base_units, to_ui_amount};
+
+
+#[derive(Parser)]
+#[clap(name = "vestor", about = "Operate the vestor program")]
+struct Cli {
+    /// RPC endpoint
+    #[clap(long, global = true, default_value = "https://api.devnet.solana.com")]
+    url: String,
+
+    /// Keypair that signs and pays for transactions
+    #[clap(long, global = true, default_value = "~/.config/solana/id.json")]
+    keypair: String,
+
+    /// Simulate the transaction and print its logs instead of sending it
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a vestor and move tokens into the account tickets are funded from
+    Init {
+        #[clap(long)]
+        mint: Pubkey,
+        /// Token account owned by the keypair that the tokens come from
+        #[clap(long)]
+        deposit: Pubkey,
+        /// Token account tickets will later be funded from
+        #[clap(long)]
+        ticket_creator_deposit: Pubkey,
+        /// Whole tokens, e.g. 1000.5
+        #[clap(long)]
+        amount: String,
+        /// Keypair for the new vestor account, generated when omitted
+        #[clap(long)]
+        vestor_keypair: Option<String>,
+    },
+    /// Create a ticket funded from a token account owned by the keypair
+    CreateTicket {
+        #[clap(long)]
+        vestor: Pubkey,
+        #[clap(long)]
+        beneficiary: Pubkey,
+        /// Beneficiary's token account that claims are paid into
+        #[clap(long)]
+        claimant_vault: Pubkey,
+        /// Token account owned by the keypair that funds the ticket
+        #[clap(long)]
+        deposit: Pubkey,
+        /// Whole tokens, e.g. 1000.5
+        #[clap(long)]
+        amount: String,
+        #[clap(long)]
+        cliff_days: u64,
+        #[clap(long)]
+        vesting_days: u64,
+        #[clap(long)]
+        irrevocable: bool,
+    },
+    /// Claim everything vested so far. The keypair must be the ticket's claimant.
+    Claim {
+        #[clap(long)]
+        ticket: Pubkey,
+    },
+    /// Return a ticket's remaining balance. The keypair must be the ticket's owner.
+    Revoke {
+        #[clap(long)]
+        ticket: Pubkey,
+    },
+    /// Print a ticket and its vesting status
+    ShowTicket {
+        #[clap(long)]
+        ticket: Pubkey,
+    },
+    /// List every ticket of a vestor
+    ListTickets {
+        #[clap(long)]
+        vestor: Pubkey,
+    },
+    /// Create one ticket per row of a CSV with columns beneficiary, amount, cliff,
+    /// vesting, irrevocable. Re-running with the same results file resumes where it stopped.
+    BulkCreate {
+        #[clap(long)]
+        vestor: Pubkey,
+        /// Token account owned by the keypair that funds every ticket
+        #[clap(long)]
+        deposit: Pubkey,
+        #[clap(long)]
+        csv: String,
+        /// Maps CSV rows to ticket addresses; read on start to skip rows already created
+        #[clap(long)]
+        results: String,
+        /// Tickets per transaction. Each ticket adds two signers, four instructions and up to
+        /// five accounts, so a second one no longer fits in a transaction's 1232 bytes.
+        #[clap(long, default_value = "1")]
+        batch_size: usize,
+    },
+}
+
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url.clone(), CommitmentConfig::confirmed());
+    let ctx = Ctx { rpc, keypair_path: expand_home(&cli.keypair), dry_run: cli.dry_run };
+
+    match cli.command {
+        Command::Init { mint, deposit, ticket_creator_deposit, amount, vestor_keypair } => {
+            init(&ctx, mint, deposit, ticket_creator_deposit, &amount, vestor_keypair)
+        }
+        Command::CreateTicket {
+            vestor,
+            beneficiary,
+            claimant_vault,
+            deposit,
+            amount,
+            cliff_days,
+            vesting_days,
+            irrevocable,
+        } => create_ticket(
+            &ctx,
+            vestor,
+            deposit,
+            claimant_vault,
+            TicketTerms {
+                beneficiary,
+                cliff: cliff_days,
+                vesting: vesting_days,
+                amount: 0,
+                irrevocable,
+            },
+            &amount,
+        ),
+        Command::Claim { ticket } => claim(&ctx, ticket),
+        Command::Revoke { ticket } => revoke(&ctx, ticket),
+        Command::ShowTicket { ticket } => show_ticket(&ctx, ticket),
+        Command::ListTickets { vestor } => list_tickets(&ctx, vestor),
+        Command::BulkCreate { vestor, deposit, csv, results, batch_size } => {
+            bulk::run(&ctx, vestor, deposit, &csv, &results, batch_size)
+        }
+    }
+}
+
+
+struct Ctx {
+    rpc: RpcClient,
+    keypair_path: String,
+    dry_run: bool,
+}
+
+impl Ctx {
+    fn payer(&self) -> Result<Keypair> {
+        read_keypair(&self.keypair_path)
+    }
+
+    fn account_data(&self, address: &Pubkey) -> Result<Vec<u8>> {
+        Ok(self.rpc.get_account(address).with_context(|| format!("fetching {}", address))?.data)
+    }
+
+    fn ticket(&self, address: &Pubkey) -> Result<Ticket> {
+        state::ticket(&self.account_data(address)?).map_err(|e| anyhow!("{} is not a ticket: {}", address, e))
+    }
+
+    fn vestor(&self, address: &Pubkey) -> Result<vestor_client::Vestor> {
+        state::vestor(&self.account_data(address)?).map_err(|e| anyhow!("{} is not a vestor: {}", address, e))
+    }
+
+    fn mint_decimals(&self, mint: &Pubkey) -> Result<u8> {
+        Ok(spl_token::state::Mint::unpack(&self.account_data(mint)?)?.decimals)
+    }
+
+    fn token_account(&self, address: &Pubkey) -> Result<spl_token::state::Account> {
+        Ok(spl_token::state::Account::unpack(&self.account_data(address)?)?)
+    }
+
+    // Cluster time from the Clock sysvar, which is what the program vests against
+    fn now(&self) -> Result<i64> {
+        let account = self.rpc.get_account(&sysvar::clock::id())?;
+        let clock: Clock = account::from_account(&account)
+            .ok_or_else(|| anyhow!("could not decode the Clock sysvar"))?;
+        Ok(clock.unix_timestamp)
+    }
+
+    // Signs and submits in one go
+    fn send(&self, instructions: &[Instruction], payer: &Keypair, signers: &[&Keypair]) -> Result<Option<Signature>> {
+        let (tx, _) = self.sign(instructions, payer, signers)?;
+        self.submit(&tx)
+    }
+
+    // Signs against the latest blockhash. Also returns the last block height at which the
+    // transaction can still land.
+    fn sign(&self, instructions: &[Instruction], payer: &Keypair, signers: &[&Keypair]) -> Result<(Transaction, u64)> {
+        let mut all_signers = vec![payer];
+        all_signers.extend_from_slice(signers);
+
+        let (blockhash, last_valid_block_height) = self.rpc.get_latest_blockhash_with_commitment(self.rpc.commitment())?;
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+        check_size(&tx)?;
+        Ok((tx, last_valid_block_height))
+    }
+
+    // Returns the signature, or None when the transaction was only simulated
+    fn submit(&self, tx: &Transaction) -> Result<Option<Signature>> {
+        if self.dry_run {
+            let result = self.rpc.simulate_transaction(tx)?.value;
+            for log in result.logs.unwrap_or_default() {
+                println!("  {}", log);
+            }
+            match result.err {
+                Some(err) => bail!("simulation failed: {}", err),
+                None => println!("Simulation succeeded, nothing was sent"),
+            }
+            Ok(None)
+        } else {
+            let signature = self.rpc.send_and_confirm_transaction(tx)?;
+            println!("Signature: {}", signature);
+            Ok(Some(signature))
+        }
+    }
+}
+
+
+// The cluster drops transactions larger than a packet, so catch them before sending
+fn check_size(tx: &Transaction) -> Result<()> {
+    let size = bincode::serialized_size(tx)? as usize;
+    if size > PACKET_DATA_SIZE {
+        bail!("the transaction is {} bytes, more than the {} a transaction can hold", size, PACKET_DATA_SIZE);
+    }
+    Ok(())
+}
+
+
+fn init(
+    ctx: &Ctx,
+    mint: Pubkey,
+    deposit: Pubkey,
+    ticket_creator_deposit: Pubkey,
+    amount: &str,
+    vestor_keypair: Option<String>,
+) -> Result<()> {
+    let payer = ctx.payer()?;
+    let vestor = match vestor_keypair {
+        Some(path) => read_keypair(&expand_home(&path))?,
+        None => Keypair::new(),
+    };
+    let amount = to_base_units(amount, ctx.mint_decimals(&mint)?)?;
+
+    let ix = instructions::initialize(&vestor.pubkey(), &payer.pubkey(), &mint, &deposit, &ticket_creator_deposit, amount);
+    ctx.send(&[ix], &payer, &[&vestor])?;
+
+    println!("Vestor: {}", vestor.pubkey());
+    Ok(())
+}
+
+
+fn create_ticket(
+    ctx: &Ctx,
+    vestor_key: Pubkey,
+    deposit: Pubkey,
+    claimant_vault: Pubkey,
+    mut terms: TicketTerms,
+    amount: &str,
+) -> Result<()> {
+    let payer = ctx.payer()?;
+    let vestor = ctx.vestor(&vestor_key)?;
+    if vestor.tickets_issued() == u8::MAX {
+        bail!("the vestor has already issued the most tickets it can, {}", u8::MAX);
+    }
+    let mint = ctx.token_account(&deposit)?.mint;
+    terms.amount = to_base_units(amount, ctx.mint_decimals(&mint)?)?;
+
+    let ticket = Keypair::new();
+    let vault = Keypair::new();
+    let ixs = new_ticket_instructions(
+        ctx,
+        &payer.pubkey(),
+        &vestor_key,
+        vestor.tickets_issued(),
+        &NewTicket {
+            ticket: ticket.pubkey(),
+            owner: payer.pubkey(),
+            token_mint: mint,
+            ticket_creator_deposit_token_vault: deposit,
+            claimant_receive_token_vault: claimant_vault,
+            vault: vault.pubkey(),
+        },
+        &terms,
+    )?;
+    ctx.send(&ixs, &payer, &[&ticket, &vault])?;
+
+    println!("Ticket: {}", ticket.pubkey());
+    println!("Vault:  {}", vault.pubkey());
+    Ok(())
+}
+
+
+// Allocates the ticket account and its vault, then creates the ticket. The ticket and
+// vault keypairs in `new_ticket` must sign.
+fn new_ticket_instructions(
+    ctx: &Ctx,
+    payer: &Pubkey,
+    vestor_key: &Pubkey,
+    signer_seed: u8,
+    new_ticket: &NewTicket,
+    terms: &TicketTerms,
+) -> Result<Vec<Instruction>> {
+    let ticket_rent = ctx.rpc.get_minimum_balance_for_rent_exemption(TICKET_SPACE)?;
+    let vault_rent = ctx.rpc.get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+
+    ticket_instructions(payer, vestor_key, signer_seed, new_ticket, terms, ticket_rent, vault_rent)
+}
+
+
+// `new_ticket_instructions` with the rent for the ticket and vault accounts already known
+fn ticket_instructions(
+    payer: &Pubkey,
+    vestor_key: &Pubkey,
+    signer_seed: u8,
+    new_ticket: &NewTicket,
+    terms: &TicketTerms,
+    ticket_rent: u64,
+    vault_rent: u64,
+) -> Result<Vec<Instruction>> {
+    let (signer, _) = pda::ticket_signer(&new_ticket.ticket, signer_seed);
+
+    Ok(vec![
+        system_instruction::create_account(
+            payer,
+            &new_ticket.ticket,
+            ticket_rent,
+            TICKET_SPACE as u64,
+            &vestor_client::ID,
+        ),
+        system_instruction::create_account(
+            payer,
+            &new_ticket.vault,
+            vault_rent,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(&spl_token::id(), &new_ticket.vault, &new_ticket.token_mint, &signer)?,
+        instructions::create_ticket(vestor_key, signer_seed, new_ticket, terms),
+    ])
+}
+
+
+fn claim(ctx: &Ctx, ticket_key: Pubkey) -> Result<()> {
+    let payer = ctx.payer()?;
+    let ticket = ctx.ticket(&ticket_key)?;
+    if ticket.claimant != payer.pubkey() {
+        bail!("the keypair is not this ticket's claimant ({})", ticket.claimant);
+    }
+    let vestor = ctx.vestor(&ticket.vestor)?;
+
+    let decimals = ctx.mint_decimals(&ticket.token_mint)?;
+    let claimable = vestor_client::claimable(&ticket, ctx.now()?).map_err(|e| anyhow!("{:?}", e))?;
+    println!("Claimable now: {}", ui_claimable(claimable, decimals));
+
+    ctx.send(&[instructions::claim(&ticket_key, &ticket, &vestor)], &payer, &[])?;
+    Ok(())
+}
+
+
+fn revoke(ctx: &Ctx, ticket_key: Pubkey) -> Result<()> {
+    let payer = ctx.payer()?;
+    let ticket = ctx.ticket(&ticket_key)?;
+    if ticket.owner != payer.pubkey() {
+        bail!("the keypair is not this ticket's owner ({})", ticket.owner);
+    }
+    if ticket.irrevocable {
+        bail!("ticket {} is irrevocable", ticket_key);
+    }
+
+    let decimals = ctx.mint_decimals(&ticket.token_mint)?;
+    println!("Returning: {}", to_ui_amount(ticket.balance, decimals));
+
+    ctx.send(&[instructions::revoke(&ticket_key, &ticket)], &payer, &[])?;
+    Ok(())
+}
+
+
+fn show_ticket(ctx: &Ctx, ticket_key: Pubkey) -> Result<()> {
+    let ticket = ctx.ticket(&ticket_key)?;
+    let decimals = ctx.mint_decimals(&ticket.token_mint)?;
+    let status = vestor_client::status(&ticket, ctx.now()?).map_err(|e| anyhow!("{:?}", e))?;
+    let tokens = |amount| to_ui_amount(amount, decimals);
+
+    println!("Ticket:          {}", ticket_key);
+    println!("Vestor:          {}", ticket.vestor);
+    println!("Owner:           {}", ticket.owner);
+    println!("Claimant:        {}", ticket.claimant);
+    println!("Mint:            {}", ticket.token_mint);
+    println!("Cliff:           {} days", ticket.cliff);
+    println!("Vesting:         {} days", ticket.vesting);
+    println!("Amount:          {}", tokens(ticket.amount));
+    println!("Claimed:         {}", tokens(ticket.claimed));
+    println!("Balance:         {}", tokens(ticket.balance));
+    println!("Vested:          {}", tokens(status.vested));
+    if ticket.usd_amount > 0 {
+        println!("USD amount:      ${}", to_ui_amount(ticket.usd_amount, USD_DECIMALS));
+        println!("USD paid:        ${}", to_ui_amount(ticket.usd_paid, USD_DECIMALS));
+        println!("Claimable:       ${}", to_ui_amount(status.usd_claimable, USD_DECIMALS));
+    } else {
+        println!("Claimable:       {}", tokens(status.claimable));
+    }
+    println!("Locked:          {}", tokens(status.locked));
+    println!("Next unlock:     {}", timestamp(status.next_unlock_at));
+    println!("Fully vested at: {}", timestamp(status.fully_vested_at));
+    println!("Irrevocable:     {}", ticket.irrevocable);
+    println!("Revoked:         {}", ticket.is_revoked);
+    println!("Paused:          {}", ticket.is_paused);
+
+    Ok(())
+}
+
+
+fn list_tickets(ctx: &Ctx, vestor: Pubkey) -> Result<()> {
+    let filters = vec![
+        RpcFilterType::Memcmp(Memcmp {
+            offset: 0,
+            bytes: MemcmpEncodedBytes::Bytes(state::ticket_discriminator().to_vec()),
+            encoding: None,
+        }),
+        RpcFilterType::Memcmp(Memcmp {
+            offset: state::TICKET_VESTOR_OFFSET,
+            bytes: MemcmpEncodedBytes::Bytes(vestor.to_bytes().to_vec()),
+            encoding: None,
+        }),
+    ];
+    let accounts = ctx.rpc.get_program_accounts_with_config(
+        &vestor_client::ID,
+        RpcProgramAccountsConfig { filters: Some(filters), ..RpcProgramAccountsConfig::default() },
+    )?;
+
+    let now = ctx.now()?;
+    println!("{:<44}  {:<44}  {:>20}  {:>20}  {:>20}", "TICKET", "CLAIMANT", "AMOUNT", "CLAIMED", "CLAIMABLE");
+    for (key, account) in accounts {
+        let ticket = match state::ticket(&account.data) {
+            Ok(ticket) => ticket,
+            Err(_) => continue,
+        };
+        let decimals = ctx.mint_decimals(&ticket.token_mint)?;
+        let claimable = vestor_client::claimable(&ticket, now).map_err(|e| anyhow!("{:?}", e))?;
+
+        println!(
+            "{:<44}  {:<44}  {:>20}  {:>20}  {:>20}",
+            key,
+            ticket.claimant,
+            to_ui_amount(ticket.amount, decimals),
+            to_ui_amount(ticket.claimed, decimals),
+            ui_claimable(claimable, decimals),
+        );
+    }
+
+    Ok(())
+}
+
+
+// USD-denominated tickets are owed dollars, paid in tokens at the oracle price on claim
+fn ui_claimable(claimable: Claimable, decimals: u8) -> String {
+    match claimable {
+        Claimable::Tokens(amount) => to_ui_amount(amount, decimals),
+        Claimable::Usd(usd) => format!("${}", to_ui_amount(usd, USD_DECIMALS)),
+    }
+}
+
+
+fn read_keypair(path: &str) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| anyhow!("reading keypair {}: {}", path, e))
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    }
+}
+
+fn timestamp(unix: u64) -> String {
+    if unix == 0 {
+        "-".to_string()
+    } else {
+        format!("{} (unix)", unix)
+    }
+}
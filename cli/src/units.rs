@@ -0,0 +1,63 @@
+// Conversions between token base units and the decimal amounts operators type and read
+use anyhow::{anyhow, bail, Result};
+
+
+// "12.5" with 6 decimals is 12_500_000 base units
+pub fn to_base_units(amount: &str, decimals: u8) -> Result<u64> {
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (amount, ""),
+    };
+    if fraction.len() > decimals as usize {
+        bail!("{} has more than {} decimal places", amount, decimals);
+    }
+    if whole.is_empty() && fraction.is_empty() {
+        bail!("invalid amount {:?}", amount);
+    }
+
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    digits.parse::<u64>().map_err(|_| anyhow!("invalid amount {:?}", amount))
+}
+
+pub fn to_ui_amount(base_units: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return base_units.to_string();
+    }
+
+    let unit = 10u128.pow(decimals as u32);
+    let whole = base_units as u128 / unit;
+    let fraction = base_units as u128 % unit;
+    let fraction = format!("{:0>width$}", fraction, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_human_amounts() {
+        assert_eq!(to_base_units("12.5", 6).unwrap(), 12_500_000);
+        assert_eq!(to_base_units("12", 6).unwrap(), 12_000_000);
+        assert_eq!(to_base_units(".000001", 6).unwrap(), 1);
+        assert_eq!(to_base_units("7", 0).unwrap(), 7);
+        assert!(to_base_units("0.0000001", 6).is_err());
+        assert!(to_base_units("1.2.3", 6).is_err());
+        assert!(to_base_units(".", 6).is_err());
+    }
+
+    #[test]
+    fn formats_base_units() {
+        assert_eq!(to_ui_amount(12_500_000, 6), "12.5");
+        assert_eq!(to_ui_amount(12_000_000, 6), "12");
+        assert_eq!(to_ui_amount(1, 6), "0.000001");
+        assert_eq!(to_ui_amount(7, 0), "7");
+    }
+}
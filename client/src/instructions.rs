@@ -0,0 +1,192 @@
+// Instruction builders. Every account the program can derive from on-chain state is
+// taken from the `Vestor` and `Ticket` passed in, so callers only supply what is new.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{InstructionData, ToAccountMetas};
+
+use vestor::{accounts, instruction, Ticket, Vestor};
+
+use crate::pda;
+
+
+// Bytes to allocate for a ticket account before `create_ticket` initializes it
+pub const TICKET_SPACE: usize = 8 + std::mem::size_of::<Ticket>();
+
+
+pub struct TicketTerms {
+    pub beneficiary: Pubkey,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub amount: u64,
+    pub irrevocable: bool,
+}
+
+
+// Accounts a new ticket needs that do not exist on-chain yet. `ticket` must already be
+// allocated with `TICKET_SPACE` bytes and owned by the program, and `vault` must be a
+// token account owned by `pda::ticket_signer(ticket, signer_seed)`.
+pub struct NewTicket {
+    pub ticket: Pubkey,
+    pub owner: Pubkey,
+    pub token_mint: Pubkey,
+    pub ticket_creator_deposit_token_vault: Pubkey,
+    pub claimant_receive_token_vault: Pubkey,
+    pub vault: Pubkey,
+}
+
+
+// Creates the vestor account `vestor_key` (which must sign) and moves `amount` from the
+// owner's deposit into the account tickets will be funded from
+pub fn initialize(
+    vestor_key: &Pubkey,
+    owner: &Pubkey,
+    token_mint: &Pubkey,
+    contract_owner_deposit_token_vault: &Pubkey,
+    ticket_creator_deposit_token_vault: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: vestor::ID,
+        accounts: accounts::Initialize {
+            vestor: *vestor_key,
+            contract_owner_deposit_token_vault: *contract_owner_deposit_token_vault,
+            ticket_creator_deposit_token_vault: *ticket_creator_deposit_token_vault,
+            token_mint: *token_mint,
+            owner: *owner,
+            token_program: spl_token::id(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Initialize { amount }.data(),
+    }
+}
+
+
+// `signer_seed` is `vestor.tickets_issued()` when the instruction executes: the vestor's
+// current count for a single ticket, plus one for each ticket created ahead of it in the
+// same transaction.
+pub fn create_ticket(vestor_key: &Pubkey, signer_seed: u8, new_ticket: &NewTicket, terms: &TicketTerms) -> Instruction {
+    let (signer, bump) = pda::ticket_signer(&new_ticket.ticket, signer_seed);
+
+    Instruction {
+        program_id: vestor::ID,
+        accounts: accounts::CreateTicket {
+            ticket: new_ticket.ticket,
+            owner: new_ticket.owner,
+            signer,
+            token_mint: new_ticket.token_mint,
+            ticket_creator_deposit_token_vault: new_ticket.ticket_creator_deposit_token_vault,
+            claimant_receive_token_vault: new_ticket.claimant_receive_token_vault,
+            vault: new_ticket.vault,
+            token_program: spl_token::id(),
+            vestor: *vestor_key,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::CreateTicket {
+            beneficiary: terms.beneficiary,
+            cliff: terms.cliff,
+            vesting: terms.vesting,
+            amount: terms.amount,
+            irrevocable: terms.irrevocable,
+            bump,
+        }
+        .data(),
+    }
+}
+
+
+// Signed by `ticket.claimant`, who also pays for the vestor's ChainlinkValue PDAs on the first claim
+pub fn claim(ticket_key: &Pubkey, ticket: &Ticket, vestor: &Vestor) -> Instruction {
+    let (signer, _) = pda::ticket_signer(ticket_key, ticket.signer_seed);
+    let oracles = &vestor.oracles;
+
+    Instruction {
+        program_id: vestor::ID,
+        accounts: accounts::Claim {
+            signer,
+            ticket: *ticket_key,
+            vestor: ticket.vestor,
+            token_mint: ticket.token_mint,
+            pda_deposit_token_vault: ticket.vault,
+            claimant_receive_token_vault: ticket.claimant_receive_token_vault,
+            claimant: ticket.claimant,
+            randomness_feed: vestor.randomness_feed,
+            chainlink_sol_feed: oracles.chainlink_sol_feed,
+            chainlink_eth_feed: oracles.chainlink_eth_feed,
+            chainlink_program: oracles.chainlink_program,
+            pyth_account: oracles.pyth_sol_price,
+            usd_price_feed: ticket.usd_price_feed,
+            system_program: system_program::ID,
+            chainlink_sol_value: pda::chainlink_value(&ticket.vestor, &oracles.chainlink_sol_feed).0,
+            chainlink_eth_value: pda::chainlink_value(&ticket.vestor, &oracles.chainlink_eth_feed).0,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: instruction::Claim {}.data(),
+    }
+}
+
+
+// Signed by `ticket.owner`; the balance goes back to the account the ticket was funded from
+pub fn revoke(ticket_key: &Pubkey, ticket: &Ticket) -> Instruction {
+    let (signer, _) = pda::ticket_signer(ticket_key, ticket.signer_seed);
+
+    Instruction {
+        program_id: vestor::ID,
+        accounts: accounts::Revoke {
+            signer,
+            vestor: ticket.vestor,
+            ticket_creator: ticket.owner,
+            ticket: *ticket_key,
+            token_mint: ticket.token_mint,
+            ticket_creator_deposit_token_vault: ticket.creator_deposit_token_vault,
+            pda_deposit_token_vault: ticket.vault,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: instruction::Revoke {}.data(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket() -> Ticket {
+        Ticket {
+            vestor: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            claimant: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            creator_deposit_token_vault: Pubkey::new_unique(),
+            claimant_receive_token_vault: Pubkey::new_unique(),
+            signer_seed: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn claim_is_signed_by_the_claimant_only() {
+        let ticket_key = Pubkey::new_unique();
+        let ticket = ticket();
+        let ix = claim(&ticket_key, &ticket, &Vestor::default());
+
+        let signers: Vec<Pubkey> = ix.accounts.iter().filter(|m| m.is_signer).map(|m| m.pubkey).collect();
+        assert_eq!(signers, vec![ticket.claimant]);
+        assert_eq!(ix.accounts[0].pubkey, pda::ticket_signer(&ticket_key, 3).0);
+    }
+
+    #[test]
+    fn revoke_returns_to_the_funding_account() {
+        let ticket_key = Pubkey::new_unique();
+        let ticket = ticket();
+        let ix = revoke(&ticket_key, &ticket);
+
+        assert!(ix.accounts.iter().any(|m| m.pubkey == ticket.owner && m.is_signer));
+        assert!(ix.accounts.iter().any(|m| m.pubkey == ticket.creator_deposit_token_vault && m.is_writable));
+    }
+}
@@ -0,0 +1,67 @@
+// Rust client for the vestor program. Builds `create_ticket`, `claim` and `revoke`
+// instructions from on-chain state, derives the program's PDAs and decodes its accounts.
+// Vesting math is the program's own `schedule` module, so claimable amounts computed
+// off-chain match what a claim would pay.
+pub mod instructions;
+pub mod pda;
+pub mod state;
+
+pub use vestor::schedule::{self, VestingSchedule, VestingStatus, SECONDS_PER_DAY};
+pub use vestor::{ChainlinkValue, OracleRegistry, Ticket, Vestor, ID};
+
+use anchor_lang::prelude::*;
+
+
+// USD amounts on tickets are micro-dollars
+pub const USD_DECIMALS: u8 = 6;
+
+
+// What a claim would pay. USD-denominated tickets are owed dollars, which `claim` converts
+// to tokens at the oracle price when it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Claimable {
+    // Token base units
+    Tokens(u64),
+    // Micro-dollars
+    Usd(u64),
+}
+
+
+// What `claim` would pay out for `ticket` at unix time `now`
+pub fn claimable(ticket: &Ticket, now: i64) -> std::result::Result<Claimable, schedule::ScheduleError> {
+    let status = status(ticket, now)?;
+    if ticket.usd_amount > 0 {
+        Ok(Claimable::Usd(status.usd_claimable))
+    } else {
+        Ok(Claimable::Tokens(status.claimable))
+    }
+}
+
+// The ticket's full vesting snapshot at unix time `now`, as `get_vesting_status` returns it
+pub fn status(ticket: &Ticket, now: i64) -> std::result::Result<VestingStatus, schedule::ScheduleError> {
+    ticket.schedule().status(now)
+}
+
+// Program id the client builds instructions for
+pub fn program_id() -> Pubkey {
+    ID
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(usd_amount: u64) -> Ticket {
+        Ticket { amount: 1000, balance: 1000, vesting: 100, usd_amount, ..Default::default() }
+    }
+
+    #[test]
+    fn usd_tickets_are_claimable_in_usd() {
+        let now = (50 * SECONDS_PER_DAY) as i64;
+
+        assert_eq!(claimable(&ticket(0), now).unwrap(), Claimable::Tokens(500));
+        assert_eq!(claimable(&ticket(2_000_000), now).unwrap(), Claimable::Usd(1_000_000));
+        assert_eq!(claimable(&Ticket { is_revoked: true, ..ticket(0) }, now).unwrap(), Claimable::Tokens(0));
+    }
+}
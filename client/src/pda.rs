@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use vestor::CHAINLINK_VALUE_SEED;
+
+
+// Signer PDA owning a ticket's vault. `signer_seed` is `vestor.tickets_issued()` at the
+// time the ticket is created, and `ticket.signer_seed` after that.
+pub fn ticket_signer(ticket: &Pubkey, signer_seed: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ticket.as_ref(), signer_seed.to_string().as_bytes()],
+        &vestor::ID,
+    )
+}
+
+// Latest Chainlink answer of `feed` stored for `vestor`
+pub fn chainlink_value(vestor: &Pubkey, feed: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CHAINLINK_VALUE_SEED, vestor.as_ref(), feed.as_ref()],
+        &vestor::ID,
+    )
+}
@@ -0,0 +1,27 @@
+// Decoders for raw account data, checking the Anchor discriminator first
+use anchor_lang::prelude::*;
+use anchor_lang::{AccountDeserialize, Discriminator};
+
+use vestor::{ChainlinkValue, Ticket, Vestor};
+
+
+pub fn vestor(data: &[u8]) -> Result<Vestor> {
+    Vestor::try_deserialize(&mut &data[..])
+}
+
+pub fn ticket(data: &[u8]) -> Result<Ticket> {
+    Ticket::try_deserialize(&mut &data[..])
+}
+
+pub fn chainlink_value(data: &[u8]) -> Result<ChainlinkValue> {
+    ChainlinkValue::try_deserialize(&mut &data[..])
+}
+
+
+// Offset of `Ticket::vestor`, after the discriminator and `token_mint`. Used to filter
+// program accounts down to one vestor's tickets.
+pub const TICKET_VESTOR_OFFSET: usize = 8 + 32;
+
+pub fn ticket_discriminator() -> [u8; 8] {
+    Ticket::discriminator()
+}
@@ -1,51 +1,107 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::system_program;
-use anchor_lang::solana_program::{clock};
-use anchor_spl::token::{self, Mint, Token, TokenAccount, SetAuthority, Transfer};
+use anchor_lang::solana_program::{clock, hash, pubkey};
+use anchor_lang::solana_program::program::set_return_data;
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, SetAuthority, Transfer};
 use spl_token::instruction::AuthorityType;
-use chainlink_solana as chainlink;
-use pyth_client::{self, load_price, Price};
+
+mod oracle;
+use oracle::*;
+
+pub mod schedule;
+use schedule::*;
+pub mod merkle;
+
 declare_id!("8hst6KmcWGU5SDoJUQUpjNckeyQxJrsHrksXhx52x1C4");
 
 
-pub fn available(
-    ticket: &mut Box<Account<Ticket>>,
-) -> u64 {
-    if has_cliffed(ticket) {
-        return unlocked(ticket);
-    } else {
-        return 0;
-    }
+// `Clock::get` without the panic: a missing clock sysvar aborts with `ClockUnavailable`
+pub fn current_clock() -> Result<clock::Clock> {
+    Ok(clock::Clock::get().map_err(|_| ErrorCode::ClockUnavailable)?)
 }
 
 
-pub fn has_cliffed(
-    ticket: &mut Box<Account<Ticket>>,
-) -> bool {
-    let clock = clock::Clock::get().unwrap();
-    if ticket.cliff == 0 {
-        return true;
+// Feed accounts must belong to the vestor's registered Chainlink program. Only `mock-oracle`
+// builds also accept feeds owned by this program, i.e. `MockPriceFeed`s.
+#[cfg(feature = "mock-oracle")]
+pub fn is_allowed_feed_owner(owner: &Pubkey, program_id: &Pubkey, vestor: &Vestor) -> bool {
+    *owner == vestor.oracles.chainlink_program || owner == program_id
+}
+
+#[cfg(not(feature = "mock-oracle"))]
+pub fn is_allowed_feed_owner(owner: &Pubkey, _program_id: &Pubkey, vestor: &Vestor) -> bool {
+    *owner == vestor.oracles.chainlink_program
+}
+
+
+// The `PriceSource` behind a feed account: a registered Chainlink feed or, in `mock-oracle`
+// builds, a program-owned `MockPriceFeed` belonging to the same vestor.
+pub fn feed_source<'a, 'info>(
+    program_id: &Pubkey,
+    price_feed: &'a AccountInfo<'info>,
+    chainlink_program: &'a AccountInfo<'info>,
+    vestor: &Account<'info, Vestor>,
+) -> Result<Box<dyn PriceSource + 'a>> {
+    if !is_allowed_feed_owner(price_feed.owner, program_id, vestor) {
+        return Err(ErrorCode::InvalidOracleProgram.into());
     }
 
-    return  clock.unix_timestamp as u64 > ticket.created_at.checked_add(
-        ticket.cliff.checked_mul(
-            86400
-        ).unwrap()
-    ).unwrap();
+    #[cfg(feature = "mock-oracle")]
+    if price_feed.owner == program_id {
+        let mock: Account<MockPriceFeed> = Account::try_from(price_feed)?;
+        if mock.vestor != vestor.key() {
+            return Err(ErrorCode::InvalidOracleFeed.into());
+        }
+        return Ok(Box::new((*mock).clone()));
+    }
+
+    Ok(Box::new(ChainlinkSource::new(chainlink_program, price_feed, vestor.max_oracle_age)))
+}
+
+
+// Every feed price the program reads, in claims, tranches and price histories, comes from here
+pub fn read_feed_price<'info>(
+    program_id: &Pubkey,
+    price_feed: &AccountInfo<'info>,
+    chainlink_program: &AccountInfo<'info>,
+    vestor: &Account<'info, Vestor>,
+    clock: &clock::Clock,
+) -> Result<NormalizedPrice> {
+    feed_source(program_id, price_feed, chainlink_program, vestor)?
+        .price(clock.unix_timestamp as u64, clock.slot)
 }
 
 
-pub fn unlocked(
+// Marks a tranche released and unlocks its share of the ticket. Returns the released amount.
+pub fn release_tranche(
+    tranche: &mut PriceTranche,
     ticket: &mut Box<Account<Ticket>>,
-) -> u64 {
-    let clock = clock::Clock::get().unwrap();
-    
-    let timelapsed = (clock.unix_timestamp as u64).checked_sub(ticket.created_at).unwrap();  
-    let vesting_in_seconds = ticket.vesting.checked_mul(86400).unwrap();
+) -> Result<u64> {
+    let release = (ticket.amount as u128).checked_mul(tranche.release_bps as u128)
+        .ok_or(ErrorCode::MathOverflow)? / 10000;
+    let release = release as u64;
+    tranche.is_released = true;
+    ticket.milestone_unlocked = ticket.milestone_unlocked.checked_add(release)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    return Ok(release);
+}
+
+
+// Draws the early-release lottery for a ticket from the round it committed to.
+// The outcome is only known once the randomness authority publishes that round,
+// so it cannot be influenced by when the claimant sends the transaction.
+pub fn won_early_release(
+    ticket: &Ticket,
+    ticket_key: &Pubkey,
+    randomness: &RandomnessFeed,
+) -> bool {
+    if ticket.lottery_round == 0 || randomness.round != ticket.lottery_round {
+        return false;
+    }
 
-    return timelapsed.checked_mul(ticket.balance).unwrap().checked_div(
-        vesting_in_seconds as u64
-    ).unwrap();
+    let draw = hash::hashv(&[&randomness.value, ticket_key.as_ref()]);
+    return draw.to_bytes()[0] % 2 == 0;
 }
 
 
@@ -65,7 +121,21 @@ pub mod vestor_using_anchor_chainlink_master {
 
        
         ctx.accounts.vestor.tickets_issued = 0;
-
+        ctx.accounts.vestor.authority = *ctx.accounts.owner.to_account_info().key;
+        ctx.accounts.vestor.change_of_control = false;
+        ctx.accounts.vestor.max_oracle_age = DEFAULT_MAX_ORACLE_AGE;
+        ctx.accounts.vestor.pyth_max_conf_bps = DEFAULT_PYTH_MAX_CONF_BPS;
+        ctx.accounts.vestor.pyth_max_slot_age = DEFAULT_PYTH_MAX_SLOT_AGE;
+        ctx.accounts.vestor.max_oracle_deviation_bps = DEFAULT_MAX_ORACLE_DEVIATION_BPS;
+        ctx.accounts.vestor.oracles = OracleRegistry::devnet();
+        ctx.accounts.vestor.price_policy = PricePolicy::default();
+
+        emit!(VestorInitialized {
+            vestor: ctx.accounts.vestor.key(),
+            authority: ctx.accounts.vestor.authority,
+            amount,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
        
        Ok(())
 
@@ -75,23 +145,15 @@ pub mod vestor_using_anchor_chainlink_master {
    
     #[access_control(CreateTicket::accounts(&ctx, bump))]
     pub fn create_ticket(ctx: Context<CreateTicket>, beneficiary: Pubkey, cliff: u64, vesting: u64, amount: u64, irrevocable: bool  , bump : u8) -> Result<()> {
-        let clock = clock::Clock::get().unwrap();
+        let clock = current_clock()?;
         
-        if amount == 0 {
-            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
-        } if vesting < cliff {
-            return Err(ErrorCode::VestingPeriodShouldBeEqualOrLongerThanCliff.into());
-        } 
+        validate_terms(amount, cliff, vesting)?;
 
         require!(ctx.accounts.ticket_creator_deposit_token_vault.amount >= amount, ErrorCode::NotEnoughTokens);
 
         
-        let (signer, _bump_seed) = Pubkey::find_program_address(&[
-            &ctx.accounts.ticket.to_account_info().key.as_ref(),
-            &ctx.accounts.vestor.tickets_issued.to_string().as_ref()], 
-            ctx.program_id);
-        //Set authority of the Tickets to the signer pda'
-        token::set_authority(ctx.accounts.into(), AuthorityType::AccountOwner, Some(signer))?;
+        // Move the grant into the ticket's vault, which the signer PDA already owns
+        token::transfer(ctx.accounts.into_transfer_to_vault_context(), amount)?;
        
         
         let ticket = &mut ctx.accounts.ticket;
@@ -111,6 +173,9 @@ pub mod vestor_using_anchor_chainlink_master {
         ticket.token_mint = *ctx.accounts
         .token_mint
         .to_account_info().key;
+        ticket.vestor = *ctx.accounts
+        .vestor
+        .to_account_info().key;
         ticket.claimant = beneficiary;
         ticket.cliff = cliff;
         ticket.vesting = vesting;
@@ -120,10 +185,40 @@ pub mod vestor_using_anchor_chainlink_master {
         ticket.irrevocable = irrevocable;
         ticket.is_revoked = false;
         ticket.bump = bump;
+        ticket.signer_seed = ctx.accounts.vestor.tickets_issued;
         ticket.num_claims = 0;
+        ticket.is_paused = false;
+        ticket.paused_at = 0;
+        ticket.total_paused = 0;
+        ticket.acceleration_bps = 0;
+        ticket.double_trigger = false;
+        ticket.is_terminated = false;
+        ticket.terminated_at = 0;
+        ticket.accelerated = 0;
+        ticket.lottery_round = 0;
+        ticket.milestone_bps = 0;
+        ticket.milestone_unlocked = 0;
+        ticket.usd_amount = 0;
+        ticket.usd_paid = 0;
+        ticket.usd_unpaid = 0;
+        ticket.usd_price_feed = Pubkey::default();
        
 
-        ctx.accounts.vestor.tickets_issued += 1;
+        ctx.accounts.vestor.tickets_issued = ctx.accounts.vestor.tickets_issued.checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(TicketCreated {
+            ticket: ctx.accounts.ticket.key(),
+            vestor: ctx.accounts.ticket.vestor,
+            owner: ctx.accounts.ticket.owner,
+            claimant: beneficiary,
+            amount,
+            cliff,
+            vesting,
+            irrevocable,
+            timestamp: ctx.accounts.ticket.created_at,
+            balance: ctx.accounts.ticket.balance,
+        });
        
 
         Ok(())
@@ -131,148 +226,234 @@ pub mod vestor_using_anchor_chainlink_master {
 
     #[access_control(not_revoked(&ctx.accounts.ticket))]
     pub fn claim(ctx: Context<Claim>) -> Result<()> {
-        let clock = clock::Clock::get().unwrap();
-        let (_signer, bump_seed) = Pubkey::find_program_address(&[ 
-            &ctx.accounts.ticket.to_account_info().key.as_ref()], 
-            ctx.program_id);
-        let seeds = &[&ctx.accounts.ticket.to_account_info().key.as_ref()[..], &[bump_seed]];
+        let clock = current_clock()?;
+        let ticket_key = ctx.accounts.ticket.key();
+        let signer_seed = ctx.accounts.ticket.signer_seed.to_string();
+        let seeds = &[ticket_key.as_ref(), signer_seed.as_bytes(), &[ctx.accounts.ticket.bump]];
         
 
-        let sol_round = chainlink::latest_round_data(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_sol_feed.to_account_info(),
-        )?;
-
-        let sol_description = chainlink::description(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_sol_feed.to_account_info(),
-        )?;
-
-        let sol_decimals = chainlink::decimals(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_sol_feed.to_account_info(),
-        )?;
-
-        // Set the account value
-        let value_of_sol: &mut Account<ChainlinkValue> = &mut ctx.accounts.chainlink_value;
-        value_of_sol.value= sol_round.answer;
-        value_of_sol.decimals=u32::from(sol_decimals);
-
-        // Also print the SOL value to the program output
-        let value_print_sol = ChainlinkValue::new(sol_round.answer, u32::from(sol_decimals));
-        msg!("{} price is {}", sol_description, value_print_sol);
-
+        let now = clock.unix_timestamp as u64;
 
-        let eth_round = chainlink::latest_round_data(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_eth_feed.to_account_info(),
-        )?;
+        // Each read is kept as a Result so the aggregator can skip sources that are down.
+        // The feeds are read as `PriceSource`s, so a `MockPriceFeed` can stand in for either.
+        let sol_reading = read_feed_price(
+            ctx.program_id,
+            &ctx.accounts.chainlink_sol_feed,
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.vestor,
+            &clock,
+        );
+
+        if let Ok(value_print_sol) = &sol_reading {
+            // Set the account value
+            ctx.accounts.chainlink_sol_value.set_price(value_print_sol, now)?;
+
+            // Also print the SOL value to the program output
+            msg!("SOL price is {:?}", value_print_sol);
+        }
 
-        let eth_description = chainlink::description(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_eth_feed.to_account_info(),
-        )?;
 
-        let eth_decimals = chainlink::decimals(
-            ctx.accounts.chainlink_program.to_account_info(),
-            ctx.accounts.chainlink_eth_feed.to_account_info(),
-        )?;
+        let eth_reading = read_feed_price(
+            ctx.program_id,
+            &ctx.accounts.chainlink_eth_feed,
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.vestor,
+            &clock,
+        );
 
-        // Set the account value
-        let value_of_eth: &mut Account<ChainlinkValue> = &mut ctx.accounts.chainlink_value;
-        value_of_eth.value= eth_round.answer;
-        value_of_eth.decimals=u32::from(eth_decimals);
+        if let Ok(value_print_eth) = &eth_reading {
+            // Set the account value
+            ctx.accounts.chainlink_eth_value.set_price(value_print_eth, now)?;
 
-        // Also print the ETH value to the program output
-        let value_print_eth = ChainlinkValue::new(eth_round.answer, u32::from(eth_decimals));
-        msg!("{} price is {}", eth_description, value_print_eth);
+            // Also print the ETH value to the program output
+            msg!("ETH price is {:?}", value_print_eth);
+        }
 
        // Now lets console the Pyth values: 
-        let pyth_price_info = &ctx.accounts.pyth_account;
-        let pyth_price_data = &pyth_price_info.try_borrow_data()?;
-        let price_account: Price = *load_price(pyth_price_data).unwrap();
-            
-        msg!("Pyth's Sol price_account address .. {:?}", pyth_price_info.key);
-        msg!("Price_Type ... {:?}", price_account.ptype);
-        msg!("Sol price from Pyth........ {}", price_account.agg.price);
-        let value_print_sol_pyth = price_account.agg.price as i128;
-         
-        let now = clock.unix_timestamp as u64;
-       
+        let pyth_reading = PythSource::new(
+            &ctx.accounts.pyth_account,
+            ctx.accounts.vestor.pyth_max_conf_bps,
+            ctx.accounts.vestor.pyth_max_slot_age,
+        ).price(now, clock.slot);
+
+        if let Ok(value_print_sol_pyth) = &pyth_reading {
+            msg!("Pyth's Sol price_account address .. {:?}", ctx.accounts.pyth_account.key);
+            msg!("Sol price from Pyth........ {:?}", value_print_sol_pyth);
+        }
+
+        let oracles_agree = match (&pyth_reading, &sol_reading) {
+            (Ok(pyth), Ok(chainlink)) => pyth.deviation_bps(chainlink)?
+                <= ctx.accounts.vestor.max_oracle_deviation_bps,
+            _ => false,
+        };
        
+        // Vestors without a randomness feed have no lottery, so there is nothing to draw.
+        // The committed round stays on the ticket once drawn, so it cannot be requested again.
+        let lucky = if ctx.accounts.vestor.randomness_feed == Pubkey::default() {
+            false
+        } else {
+            let randomness: Account<RandomnessFeed> = Account::try_from(&ctx.accounts.randomness_feed)?;
+            won_early_release(
+                &ctx.accounts.ticket,
+                ctx.accounts.ticket.to_account_info().key,
+                &randomness,
+            )
+        };
 
-        //Lucky combination of 0 claims + randomness of Time + condition of SOL Price having crossed ETH Price (i.e Merry Christmas Time) , 
-        // && Pyth Sol Price == Chainlink Sol Price ( which is almost an impossibility)
+        //Lucky combination of 0 claims + a winning early-release draw + condition of SOL Price having crossed ETH Price (i.e Merry Christmas Time) , 
+        // && Pyth Sol Price within the vestor's allowed deviation of Chainlink Sol Price
         // Then all Tickets can be claimed before Vesting schedule Expiration. 
-        if now % 2 == 0  
-        && ctx.accounts.ticket.claimed == 0 
-        && value_print_sol.value > value_print_eth.value 
-        && value_print_sol.value == value_print_sol_pyth
+        // Only the early release needs the aggregated SOL price, so it is only taken for winners:
+        // Chainlink is the primary SOL source and Pyth the secondary, combined by the vestor's
+        // policy, and a ticket whose sources cannot be aggregated just vests on schedule.
+        let early_release = lucky
+            && ctx.accounts.ticket.usd_amount == 0
+            && ctx.accounts.ticket.claimed == 0
+            && oracles_agree
+            && {
+                let sol_readings: [Option<NormalizedPrice>; CLAIM_PRICE_SOURCES] =
+                    [sol_reading.as_ref().ok().copied(), pyth_reading.as_ref().ok().copied()];
+                match (aggregate(&sol_readings, &ctx.accounts.vestor.price_policy), &eth_reading) {
+                    (Ok(sol_price), Ok(value_print_eth)) => {
+                        msg!("Aggregated Sol price ........ {:?}", sol_price);
+                        // Chainlink and Pyth report with different decimals, so compare them normalized
+                        sol_price.is_above(value_print_eth)?
+                    }
+                    _ => false,
+                }
+            };
 
+        if early_release
             {
-                let amount = ctx.accounts.ticket.balance;
-
-                // Transfer and set authority
-                {
-                    token::transfer(ctx.accounts
-                        .into_transfer_to_claimant_context()
-                        .with_signer(&[&seeds[..]]),
-                        amount)?;
-                    
-                    token::set_authority(ctx.accounts
-                        .into_set_authority_context()
-                        .with_signer(&[&seeds[..]]), AuthorityType::AccountOwner, 
-                        Some(ctx.accounts.ticket.owner))?;
+                let mut schedule = ctx.accounts.ticket.schedule();
+                let amount = schedule.release_early()?;
 
-                }
+                token::transfer(ctx.accounts
+                    .into_transfer_to_claimant_context()
+                    .with_signer(&[&seeds[..]]),
+                    amount)?;
 
-                ctx.accounts.ticket.claimed += amount;
-                ctx.accounts.ticket.balance -= amount;
+                ctx.accounts.ticket.set_ledger(&schedule);
                 ctx.accounts.ticket.last_claimed_at = clock.unix_timestamp as u64;
-                ctx.accounts.ticket.num_claims += 1;
+                ctx.accounts.ticket.num_claims = ctx.accounts.ticket.num_claims.checked_add(1)
+                    .ok_or(ErrorCode::MathOverflow)?;
+
+                emit!(Claimed {
+                    ticket: ctx.accounts.ticket.key(),
+                    claimant: ctx.accounts.ticket.claimant,
+                    amount,
+                    usd_paid: 0,
+                    early_release: true,
+                    claimed: ctx.accounts.ticket.claimed,
+                    timestamp: ctx.accounts.ticket.last_claimed_at,
+                    balance: ctx.accounts.ticket.balance,
+                });
             
 
+            }
+        else if ctx.accounts.ticket.usd_amount > 0
+            {
+                // USD grant: convert what is owed to tokens at the price of the ticket's own mint,
+                // paying at most what is left in the vault and tracking the rest as unpaid
+                let usd_price = read_feed_price(
+                    ctx.program_id,
+                    &ctx.accounts.usd_price_feed,
+                    &ctx.accounts.chainlink_program,
+                    &ctx.accounts.vestor,
+                    &clock,
+                )?;
+                msg!("Token price ........ {:?}", usd_price);
+
+                let mint_decimals = ctx.accounts.token_mint.decimals;
+                let mut schedule = ctx.accounts.ticket.schedule();
+                let payout = schedule.claim_usd(
+                    clock.unix_timestamp,
+                    ctx.accounts.pda_deposit_token_vault.amount,
+                    |usd| usd_price.usd_to_tokens(usd, mint_decimals),
+                    |tokens| usd_price.tokens_to_usd(tokens, mint_decimals),
+                )?;
+
+                token::transfer(ctx.accounts
+                    .into_transfer_to_claimant_context()
+                    .with_signer(&[&seeds[..]]),
+                    payout.tokens)?;
+
+                ctx.accounts.ticket.set_ledger(&schedule);
+                ctx.accounts.ticket.last_claimed_at = clock.unix_timestamp as u64;
+                ctx.accounts.ticket.num_claims = ctx.accounts.ticket.num_claims.checked_add(1)
+                    .ok_or(ErrorCode::MathOverflow)?;
+
+                emit!(Claimed {
+                    ticket: ctx.accounts.ticket.key(),
+                    claimant: ctx.accounts.ticket.claimant,
+                    amount: payout.tokens,
+                    usd_paid: payout.usd,
+                    early_release: false,
+                    claimed: ctx.accounts.ticket.claimed,
+                    timestamp: ctx.accounts.ticket.last_claimed_at,
+                    balance: ctx.accounts.ticket.balance,
+                });
+
+                // Once the last dollar is paid the balance is 0, so the vault and any tokens
+                // the grant did not need go back to the ticket owner below
+                if payout.returned > 0 {
+                    emit!(UsdGrantSettled {
+                        ticket: ctx.accounts.ticket.key(),
+                        usd_paid: ctx.accounts.ticket.usd_paid,
+                        returned: payout.returned,
+                        timestamp: ctx.accounts.ticket.last_claimed_at,
+                        balance: ctx.accounts.ticket.balance,
+                    });
+                }
             }
         else 
             {
-                let amount = available(&mut ctx.accounts.ticket);
-
+                let mut schedule = ctx.accounts.ticket.schedule();
+                let amount = schedule.claim(clock.unix_timestamp)?;
 
-                // Transfer and set Authority
-                {
-                    token::transfer(ctx.accounts
-                        .into_transfer_to_claimant_context()
-                        .with_signer(&[&seeds[..]]),
-                        amount)?;
-                    
-                    token::set_authority(ctx.accounts
-                        .into_set_authority_context()
-                        .with_signer(&[&seeds[..]]), AuthorityType::AccountOwner, 
-                        Some(ctx.accounts.ticket.owner))?;
 
-                }
+                token::transfer(ctx.accounts
+                    .into_transfer_to_claimant_context()
+                    .with_signer(&[&seeds[..]]),
+                    amount)?;
 
                 ctx.accounts.ticket.claimant = *ctx.accounts
                 .claimant
                 .to_account_info().key;
-                ctx.accounts.ticket.claimed += amount;
-                ctx.accounts.ticket.balance -= amount;
+                ctx.accounts.ticket.set_ledger(&schedule);
                 ctx.accounts.ticket.last_claimed_at = clock.unix_timestamp as u64;
-                ctx.accounts.ticket.num_claims += 1;
+                ctx.accounts.ticket.num_claims = ctx.accounts.ticket.num_claims.checked_add(1)
+                    .ok_or(ErrorCode::MathOverflow)?;
+
+                emit!(Claimed {
+                    ticket: ctx.accounts.ticket.key(),
+                    claimant: ctx.accounts.ticket.claimant,
+                    amount,
+                    usd_paid: 0,
+                    early_release: false,
+                    claimed: ctx.accounts.ticket.claimed,
+                    timestamp: ctx.accounts.ticket.last_claimed_at,
+                    balance: ctx.accounts.ticket.balance,
+                });
             }
-        
-       
+
+        // Hand the emptied vault back to the ticket owner
+        if ctx.accounts.ticket.balance == 0 {
+            token::set_authority(ctx.accounts
+                .into_set_authority_context()
+                .with_signer(&[&seeds[..]]), AuthorityType::AccountOwner,
+                Some(ctx.accounts.ticket.owner))?;
+        }
 
         Ok(())
     }
 
 
     pub fn revoke(ctx: Context<Revoke>) -> Result<()> {
-        let clock = clock::Clock::get().unwrap();
-        let (_signer, bump_seed) = Pubkey::find_program_address(&[
-            &ctx.accounts.ticket.to_account_info().key.as_ref()], ctx.program_id);
-        let seeds = &[&ctx.accounts.ticket.to_account_info().key.as_ref()[..], &[bump_seed]];
-        let _clock = clock::Clock::get().unwrap();
+        let clock = current_clock()?;
+        let ticket_key = ctx.accounts.ticket.key();
+        let signer_seed = ctx.accounts.ticket.signer_seed.to_string();
+        let seeds = &[ticket_key.as_ref(), signer_seed.as_bytes(), &[ctx.accounts.ticket.bump]];
 
         if ctx.accounts.ticket.is_revoked == true {
             return Err(ErrorCode::TicketRevoked.into());
@@ -283,12 +464,15 @@ pub mod vestor_using_anchor_chainlink_master {
             return Err(ErrorCode::TicketIrrevocable.into());
         }
 
+        let mut schedule = ctx.accounts.ticket.schedule();
+        let returned = schedule.revoke()?;
+
         // Transfer.
         {
             token::transfer(ctx.accounts
                 .into_transfer_to_ticket_creator_context()
                 .with_signer(&[&seeds[..]]),
-                ctx.accounts.ticket.balance)?;
+                returned)?;
 
             token::set_authority(ctx.accounts
                 .into_set_authority_context()
@@ -296,219 +480,1147 @@ pub mod vestor_using_anchor_chainlink_master {
                 Some(ctx.accounts.ticket.owner))?;
         }
 
-        ctx.accounts.ticket.is_revoked = true;
-        ctx.accounts.ticket.balance = 0;
+        ctx.accounts.ticket.set_ledger(&schedule);
         ctx.accounts.ticket.revoked_at = clock.unix_timestamp as u64;
 
+        emit!(Revoked {
+            ticket: ctx.accounts.ticket.key(),
+            amount: returned,
+            claimed: ctx.accounts.ticket.claimed,
+            timestamp: ctx.accounts.ticket.revoked_at,
+            balance: ctx.accounts.ticket.balance,
+        });
+
         Ok(())
     }
 
-  
-}
 
+    pub fn init_randomness_feed(ctx: Context<InitRandomnessFeed>) -> Result<()> {
+        let feed = &mut ctx.accounts.randomness_feed;
+        feed.authority = *ctx.accounts.randomness_authority.to_account_info().key;
+        feed.round = 0;
+        feed.value = [0; 32];
+        feed.updated_at = 0;
 
+        ctx.accounts.vestor.randomness_feed = *ctx.accounts.randomness_feed.to_account_info().key;
 
+        emit!(RandomnessFeedSet {
+            vestor: ctx.accounts.vestor.key(),
+            randomness_feed: ctx.accounts.vestor.randomness_feed,
+            authority: ctx.accounts.randomness_feed.authority,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
 
+        Ok(())
+    }
 
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    // Total 7 accounts used in Initialization of the Program :
-    // vestor being created
-   #[account(init, payer = owner, space = 8 + 8)]
-    pub vestor : Box<Account<'info, Vestor>>,
+    // Called by the VRF oracle (or a local mock signer in tests) with each new verified value
+    pub fn publish_randomness(ctx: Context<PublishRandomness>, value: [u8; 32]) -> Result<()> {
+        let clock = current_clock()?;
+        let feed = &mut ctx.accounts.randomness_feed;
 
-    #[account(mut, has_one = owner, constraint = contract_owner_deposit_token_vault.mint == token_mint.key())]
-    pub contract_owner_deposit_token_vault : Box<Account<'info, TokenAccount>>, 
+        feed.round = feed.round.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        feed.value = value;
+        feed.updated_at = clock.unix_timestamp as u64;
 
-    #[account(mut,
-        constraint = ticket_creator_deposit_token_vault.mint == contract_owner_deposit_token_vault.mint)]
-    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+        emit!(RandomnessPublished {
+            randomness_feed: ctx.accounts.randomness_feed.key(),
+            round: ctx.accounts.randomness_feed.round,
+            value,
+            timestamp: ctx.accounts.randomness_feed.updated_at,
+        });
 
-    pub token_mint : Box<Account<'info, Mint>>, 
+        Ok(())
+    }
 
-    // The Owner of contract_owner_deposit_token_vault
-    #[account(mut)]
-    pub owner : Signer<'info>,
-    
-    pub token_program : Program<'info, Token>, 
 
-    pub system_program : Program<'info, System>, 
+    // Commit phase of the early-release lottery: the claimant is bound to the next, still
+    // unpublished, randomness round. The draw is revealed by `claim` once that round exists.
+    // A ticket gets a single draw: the committed round is never cleared, win or lose.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn request_early_release(ctx: Context<RequestEarlyRelease>) -> Result<()> {
+        let ticket = &mut ctx.accounts.ticket;
 
-     
-}
+        if ticket.claimed != 0 {
+            return Err(ErrorCode::EarlyReleaseUnavailable.into());
+        }
+        if ticket.lottery_round != 0 {
+            return Err(ErrorCode::EarlyReleaseAlreadyRequested.into());
+        }
 
-    impl<'info> Initialize<'info> {
-        pub fn has_access(_ctx: &Context<Initialize>) -> Result<()> {
-         // TODO : add some whitelist admins
-         
-          Ok(())
+        ticket.lottery_round = ctx.accounts.randomness_feed.round.checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(EarlyReleaseRequested {
+            ticket: ctx.accounts.ticket.key(),
+            lottery_round: ctx.accounts.ticket.lottery_round,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    // A TWAP tranche (`twap_window > 0`) takes the vestor's price history for `feed` as its
+    // first remaining account, and the window must fit in what that history can hold.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn add_price_tranche(
+        ctx: Context<AddPriceTranche>,
+        feed: Pubkey,
+        strike: i128,
+        strike_decimals: u32,
+        release_bps: u16,
+        hold_seconds: u64,
+        twap_window: u64,
+    ) -> Result<()> {
+        if ctx.accounts.ticket.usd_amount > 0 {
+            return Err(ErrorCode::BonusUnlockOnUsdTicket.into());
+        }
+        let milestone_bps = ctx.accounts.ticket.milestone_bps.checked_add(release_bps)
+            .ok_or(ErrorCode::InvalidBasisPoints)?;
+        if release_bps == 0 || milestone_bps > 10000 {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        if strike <= 0 {
+            return Err(ErrorCode::InvalidStrikePrice.into());
+        }
+        if twap_window != 0 {
+            let history_info = ctx.remaining_accounts.first().ok_or(ErrorCode::InsufficientPriceHistory)?;
+            let history: Account<PriceHistory> = Account::try_from(history_info)?;
+            if history.vestor != ctx.accounts.ticket.vestor || history.feed != feed {
+                return Err(ErrorCode::InvalidOracleFeed.into());
+            }
+            if twap_window > history.max_window()? {
+                return Err(ErrorCode::InsufficientPriceHistory.into());
+            }
         }
-      } 
 
+        let tranche = &mut ctx.accounts.price_tranche;
+        tranche.ticket = *ctx.accounts.ticket.to_account_info().key;
+        tranche.feed = feed;
+        tranche.strike = strike;
+        tranche.strike_decimals = strike_decimals;
+        tranche.release_bps = release_bps;
+        tranche.hold_seconds = hold_seconds;
+        tranche.twap_window = twap_window;
+        tranche.above_since = 0;
+        tranche.is_released = false;
+
+        ctx.accounts.ticket.milestone_bps = milestone_bps;
+
+        emit!(PriceTrancheAdded {
+            ticket: ctx.accounts.ticket.key(),
+            price_tranche: ctx.accounts.price_tranche.key(),
+            feed,
+            strike,
+            strike_decimals,
+            release_bps,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
 
-#[derive(Accounts)]
-pub struct CreateTicket<'info> {
-    // Total 10 accounts used in 'Create'
-    //ticket being created
-    #[account(zero)]
-    pub ticket : Box<Account<'info, Ticket>>,
+        Ok(())
+    }
 
-    /// CHECK : The Owner of ticket_creator_deposit_token_vault
-    pub owner : AccountInfo<'info>,
 
-    ///CHECK: Program Derived address (PDA) for the Ticket
-    #[account(
-        seeds = [
-            ticket.to_account_info().key.as_ref(),
-            vestor.tickets_issued.to_string().as_ref()
-            ],
-        bump = ticket.bump,
-    )]
-    pub signer : AccountInfo<'info>, 
+    // Permissionless crank: anyone may report the tranche's feed. The tranche releases once
+    // the price has stayed at or above the strike for `hold_seconds`.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn check_price_tranche(ctx: Context<CheckPriceTranche>) -> Result<()> {
+        let clock = current_clock()?;
+        let now = clock.unix_timestamp as u64;
 
-    pub token_mint : Box<Account<'info, Mint>>, 
+        if ctx.accounts.price_tranche.is_released == true {
+            return Err(ErrorCode::TrancheAlreadyReleased.into());
+        }
 
-    // This is the 'from' token
-    #[account(mut, has_one = owner )]
-    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+        if ctx.accounts.price_tranche.twap_window != 0 {
+            return Err(ErrorCode::TrancheRequiresTwap.into());
+        }
 
-    //This is the 'to' token
-    #[account(mut, 
-        constraint = claimant_receive_token_vault.mint == ticket_creator_deposit_token_vault.mint)]
-    pub claimant_receive_token_vault : Box<Account<'info, TokenAccount>>, 
-    
-    // Ticket's token vault owned by the 'signer PDA'. This is the intermediate/temp token account. 
-    #[account(mut, 
-        constraint = &vault.owner == signer.key)]
-    pub vault: Box<Account<'info, TokenAccount>>,
-    
-    /// CHECK : The Token program
-    pub token_program : AccountInfo<'info>, 
+        let price = read_feed_price(
+            ctx.program_id,
+            &ctx.accounts.price_feed,
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.vestor,
+            &clock,
+        )?;
 
-    pub vestor : Box<Account<'info, Vestor>>,
+        let tranche = &mut ctx.accounts.price_tranche;
+        let strike = NormalizedPrice::new(tranche.strike, -(tranche.strike_decimals as i32));
+        if !price.is_at_or_above(&strike)? {
+            tranche.above_since = 0;
+            return Ok(());
+        }
+        if tranche.above_since == 0 {
+            tranche.above_since = now;
+        }
+        if now.saturating_sub(tranche.above_since) < tranche.hold_seconds {
+            return Ok(());
+        }
 
-    /// CHECK : the System Program 
-    pub system_program : AccountInfo<'info>, 
+        let release = release_tranche(tranche, &mut ctx.accounts.ticket)?;
+        msg!("Price tranche released {} tokens at {:?}", release, price);
 
+        emit!(PriceTrancheReleased {
+            ticket: ctx.accounts.ticket.key(),
+            price_tranche: ctx.accounts.price_tranche.key(),
+            amount: release,
+            milestone_unlocked: ctx.accounts.ticket.milestone_unlocked,
+            timestamp: now,
+            balance: ctx.accounts.ticket.balance,
+        });
 
+        Ok(())
     }
 
-    impl<'info> CreateTicket<'info> {
-        pub fn accounts(ctx: &Context<CreateTicket>, bump: u8) -> Result<()> {
-       
-            let signer_account = Pubkey::create_program_address(
-                &[ctx.accounts.ticket.to_account_info().key.as_ref(), 
-                ctx.accounts.vestor.tickets_issued.to_string().as_ref(), 
-                &[bump]], 
-                &ctx.program_id)
-                .map_err(|_| ErrorCode::InvalidNonce)?;
-  
-            if &signer_account != ctx.accounts.signer.to_account_info().key {
-                return Err(ErrorCode::InvalidProgramInitializer.into());
-            }
-  
-          Ok(())
-        }
 
-      } 
+    // Like `check_price_tranche`, but for tranches that require the time-weighted average
+    // over `twap_window` seconds, read from the feed's price history, to be above the strike.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn check_price_tranche_twap(ctx: Context<CheckPriceTrancheTwap>) -> Result<()> {
+        let clock = current_clock()?;
+        let now = clock.unix_timestamp as u64;
 
+        if ctx.accounts.price_tranche.is_released == true {
+            return Err(ErrorCode::TrancheAlreadyReleased.into());
+        }
+        if ctx.accounts.price_tranche.twap_window == 0 {
+            return Err(ErrorCode::TrancheRequiresTwap.into());
+        }
 
-   
-#[derive(Accounts)]
-pub struct Claim<'info> {  
-    // Total 14 accounts are used for 'Claim'
-    
-    /// CHECK: The 'signer PDA' is not dangerous because of seed + bump contraints
-    #[account(
-        seeds = [
-            ticket.to_account_info().key.as_ref(),
-            vestor.tickets_issued.to_string().as_ref()
-            ],
-        bump = ticket.bump,
-    )]
-    pub signer : AccountInfo<'info>, 
+        let tranche = &mut ctx.accounts.price_tranche;
+        let twap = ctx.accounts.price_history.twap(tranche.twap_window, now)?;
+        let strike = NormalizedPrice::new(tranche.strike, -(tranche.strike_decimals as i32));
+        if !twap.is_at_or_above(&strike)? {
+            return Ok(());
+        }
 
-    ///CHECK : The ticket_creator is not unsafe because some other constraints have been issued to Ticket
-    /// which ensure that ticket.creator == *ticket_creator.key
-    #[account(mut)]
-    pub ticket_creator : AccountInfo<'info>, 
+        let release = release_tranche(tranche, &mut ctx.accounts.ticket)?;
+        msg!("Price tranche released {} tokens at TWAP {:?}", release, twap);
 
-    #[account(
-        mut,
-        has_one = claimant,
-        has_one = claimant_receive_token_vault, 
-        constraint = ticket.balance > 0,
-        constraint = ticket.balance <= pda_deposit_token_vault.amount,
-        constraint = ticket.vault == *pda_deposit_token_vault.to_account_info().key, 
-        close = ticket_creator     
-    )]
-    pub ticket: Box<Account<'info, Ticket>>,
+        emit!(PriceTrancheReleased {
+            ticket: ctx.accounts.ticket.key(),
+            price_tranche: ctx.accounts.price_tranche.key(),
+            amount: release,
+            milestone_unlocked: ctx.accounts.ticket.milestone_unlocked,
+            timestamp: now,
+            balance: ctx.accounts.ticket.balance,
+        });
 
-    pub vestor : Box<Account<'info, Vestor>>,
+        Ok(())
+    }
 
-    #[account(
-        constraint = pda_deposit_token_vault.owner == signer.key(),
-    )]
-    pub pda_deposit_token_vault: Box<Account<'info, TokenAccount>>,
 
-    #[account(
-        constraint = claimant_receive_token_vault.mint == pda_deposit_token_vault.mint,
-        constraint = claimant_receive_token_vault.owner == claimant.key(),
-    )]
-    pub claimant_receive_token_vault: Box<Account<'info, TokenAccount>>,
+    // `min_interval` must be positive: `record_price` is permissionless, and without a gap
+    // between points anyone could cycle the whole history within one slot
+    pub fn init_price_history(ctx: Context<InitPriceHistory>, feed: Pubkey, expo: i32, min_interval: u64) -> Result<()> {
+        if min_interval == 0 {
+            return Err(ErrorCode::InvalidOracleConfig.into());
+        }
 
-    ///CHECK: The claimant is not unsafe because some other constraints have been issued to Ticket
-    /// which ensure that ticket.claimant == *claimant.key (see => has_one = claimant)
-    #[account(signer)]
-    pub claimant: AccountInfo<'info>,
+        let history = &mut ctx.accounts.price_history;
+        history.vestor = ctx.accounts.vestor.key();
+        history.feed = feed;
+        history.expo = expo;
+        history.min_interval = min_interval;
+        history.head = 0;
+        history.len = 0;
+        history.points = vec![PricePoint::default(); PRICE_HISTORY_LEN];
+
+        emit!(PriceHistoryInitialized {
+            vestor: ctx.accounts.vestor.key(),
+            price_history: ctx.accounts.price_history.key(),
+            feed,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
 
-    #[account(init, payer = ticket_creator, space = 100)]
-    pub chainlink_value: Account<'info, ChainlinkValue>,
+        Ok(())
+    }
 
-    ///CHECK : This account just reads the Sol Price from SOLANA_FEED ADDRESS && which arrived from the Chainlink Program
-    pub chainlink_sol_feed: AccountInfo<'info>,
 
-    ///CHECK : This account just reads the ETH Price from ETHEREUM_FEED ADDRESS && which arrived from the Chainlink Program
-    pub chainlink_eth_feed: AccountInfo<'info>,
+    // Permissionless crank appending the feed's current price to its history
+    pub fn record_price(ctx: Context<RecordPrice>) -> Result<()> {
+        let clock = current_clock()?;
 
-    /// CHECK : This is the Chainlink program's account
-    pub chainlink_program: AccountInfo<'info>,
+        let price = read_feed_price(
+            ctx.program_id,
+            &ctx.accounts.price_feed,
+            &ctx.accounts.chainlink_program,
+            &ctx.accounts.vestor,
+            &clock,
+        )?;
 
-    /// CHECK : This is the Pyth program's account
-    pub pyth_account : AccountInfo<'info>,
+        ctx.accounts.price_history.record(price, clock.unix_timestamp as u64)?;
 
-     /// CHECK : System Program address is already defined
-     #[account(address = system_program::ID)]
-    pub system_program: AccountInfo<'info>,
+        emit!(PriceRecorded {
+            price_history: ctx.accounts.price_history.key(),
+            value: price.value,
+            expo: price.expo,
+            timestamp: clock.unix_timestamp as u64,
+        });
 
-    //pub system_program: Program<'info, Token>,
-    pub token_program: Program<'info, Token>,
-}
+        Ok(())
+    }
 
 
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn amend_schedule(ctx: Context<AmendSchedule>, cliff: u64, vesting: u64) -> Result<()> {
+        validate_terms(ctx.accounts.ticket.amount, cliff, vesting)?;
 
+        let now = current_clock()?.unix_timestamp;
+        let vested_before = ctx.accounts.ticket.schedule().vested(now)?;
 
-#[derive(Accounts)]
-pub struct Revoke<'info> {
-    //Total 8 accounts used for Revoke
+        ctx.accounts.ticket.cliff = cliff;
+        ctx.accounts.ticket.vesting = vesting;
 
-    /// CHECK: The 'signer PDA' is not dangerous because of seed + bump contraints
-    #[account(
-        seeds = [
-            ticket.to_account_info().key.as_ref(),
-            vestor.tickets_issued.to_string().as_ref()
-            ],
-        bump = ticket.bump,
-    )]
-    pub signer : AccountInfo<'info>, 
+        // Both parties signed, but the new schedule may still never take back what has already vested
+        let vested_after = ctx.accounts.ticket.schedule().vested(now)?;
+        if vested_after < vested_before {
+            return Err(ErrorCode::AmendmentReducesVestedAmount.into());
+        }
 
-    pub vestor : Box<Account<'info, Vestor>>,
+        emit!(ScheduleAmended {
+            ticket: ctx.accounts.ticket.key(),
+            cliff,
+            vesting,
+            vested: vested_after,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
 
-    pub ticket_creator: Signer<'info>,
+        Ok(())
+    }
 
-    #[account(
-        mut,
+
+    // Turns a ticket into a USD-denominated grant of `usd_amount` micro-dollars. Tokens in the
+    // vault are then paid out at claim time at the oracle price, instead of vesting 1:1.
+    // Like a schedule amendment this changes the grant, so owner and claimant both sign.
+    // The vestor must have registered a price feed for the ticket's mint.
+    // USD grants only vest on their schedule, so the ticket cannot carry acceleration or tranches.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn set_usd_terms(ctx: Context<SetUsdTerms>, usd_amount: u64) -> Result<()> {
+        if usd_amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        if ctx.accounts.ticket.claimed > 0 {
+            return Err(ErrorCode::TicketAlreadyClaimed.into());
+        }
+        if ctx.accounts.ticket.acceleration_bps > 0
+            || ctx.accounts.ticket.accelerated > 0
+            || ctx.accounts.ticket.milestone_bps > 0 {
+            return Err(ErrorCode::BonusUnlockOnUsdTicket.into());
+        }
+        if ctx.accounts.mint_price_feed.data_is_empty() {
+            return Err(ErrorCode::NoMintPriceFeed.into());
+        }
+        let mint_price_feed: Account<MintPriceFeed> = Account::try_from(&ctx.accounts.mint_price_feed)?;
+
+        ctx.accounts.ticket.usd_amount = usd_amount;
+        ctx.accounts.ticket.usd_paid = 0;
+        ctx.accounts.ticket.usd_unpaid = 0;
+        ctx.accounts.ticket.usd_price_feed = mint_price_feed.feed;
+
+        emit!(UsdTermsSet {
+            ticket: ctx.accounts.ticket.key(),
+            usd_amount,
+            price_feed: mint_price_feed.feed,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn set_acceleration(ctx: Context<SetAcceleration>, acceleration_bps: u16, double_trigger: bool) -> Result<()> {
+        if acceleration_bps > 10000 {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        if acceleration_bps > 0 && ctx.accounts.ticket.usd_amount > 0 {
+            return Err(ErrorCode::BonusUnlockOnUsdTicket.into());
+        }
+
+        ctx.accounts.ticket.acceleration_bps = acceleration_bps;
+        ctx.accounts.ticket.double_trigger = double_trigger;
+
+        emit!(AccelerationSet {
+            ticket: ctx.accounts.ticket.key(),
+            acceleration_bps,
+            double_trigger,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    pub fn set_change_of_control(ctx: Context<SetChangeOfControl>, change_of_control: bool) -> Result<()> {
+        ctx.accounts.vestor.change_of_control = change_of_control;
+
+        emit!(ChangeOfControlSet {
+            vestor: ctx.accounts.vestor.key(),
+            change_of_control,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    pub fn set_oracle_config(
+        ctx: Context<ConfigureVestor>,
+        max_oracle_age: u64,
+        pyth_max_conf_bps: u64,
+        pyth_max_slot_age: u64,
+        max_oracle_deviation_bps: u64,
+    ) -> Result<()> {
+        if max_oracle_age == 0 || pyth_max_conf_bps == 0 || pyth_max_conf_bps > 10000 || pyth_max_slot_age == 0 {
+            return Err(ErrorCode::InvalidOracleConfig.into());
+        }
+        if max_oracle_deviation_bps > 10000 {
+            return Err(ErrorCode::InvalidOracleConfig.into());
+        }
+
+        ctx.accounts.vestor.max_oracle_age = max_oracle_age;
+        ctx.accounts.vestor.pyth_max_conf_bps = pyth_max_conf_bps;
+        ctx.accounts.vestor.pyth_max_slot_age = pyth_max_slot_age;
+        ctx.accounts.vestor.max_oracle_deviation_bps = max_oracle_deviation_bps;
+
+        emit!(OracleConfigUpdated {
+            vestor: ctx.accounts.vestor.key(),
+            max_oracle_age,
+            pyth_max_conf_bps,
+            pyth_max_slot_age,
+            max_oracle_deviation_bps,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // Admin-only, and only part of the program when built with the `mock-oracle` feature
+    #[cfg(feature = "mock-oracle")]
+    pub fn init_mock_price_feed(ctx: Context<InitMockPriceFeed>) -> Result<()> {
+        let feed = &mut ctx.accounts.mock_price_feed;
+        feed.vestor = ctx.accounts.vestor.key();
+        feed.value = 0;
+        feed.expo = 0;
+        feed.updated_at = 0;
+
+        Ok(())
+    }
+
+
+    // Admin-only write to a mock feed, only part of the program in `mock-oracle` builds
+    #[cfg(feature = "mock-oracle")]
+    pub fn set_mock_price(ctx: Context<SetMockPrice>, value: i128, expo: i32) -> Result<()> {
+        let clock = current_clock()?;
+        let feed = &mut ctx.accounts.mock_price_feed;
+        feed.value = value;
+        feed.expo = expo;
+        feed.updated_at = clock.unix_timestamp as u64;
+
+        Ok(())
+    }
+
+
+    pub fn set_price_policy(ctx: Context<ConfigureVestor>, price_policy: PricePolicy) -> Result<()> {
+        price_policy.validate(CLAIM_PRICE_SOURCES)?;
+
+        ctx.accounts.vestor.price_policy = price_policy;
+
+        emit!(PricePolicyUpdated {
+            vestor: ctx.accounts.vestor.key(),
+            price_policy,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // Vestors start on the devnet oracles; any other cluster needs its own registered here
+    // before tickets can be claimed.
+    pub fn set_oracle_registry(ctx: Context<ConfigureVestor>, oracles: OracleRegistry) -> Result<()> {
+        ctx.accounts.vestor.oracles = oracles;
+
+        emit!(OracleRegistryUpdated {
+            vestor: ctx.accounts.vestor.key(),
+            oracles,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // Registers the feed USD grants in `token_mint` are priced at, replacing any earlier one.
+    // Tickets keep the feed they were given by `set_usd_terms`.
+    pub fn set_mint_price_feed(ctx: Context<SetMintPriceFeed>) -> Result<()> {
+        let entry = &mut ctx.accounts.mint_price_feed;
+        entry.vestor = ctx.accounts.vestor.key();
+        entry.token_mint = ctx.accounts.token_mint.key();
+        entry.feed = ctx.accounts.price_feed.key();
+
+        emit!(MintPriceFeedSet {
+            vestor: ctx.accounts.vestor.key(),
+            token_mint: ctx.accounts.mint_price_feed.token_mint,
+            feed: ctx.accounts.mint_price_feed.feed,
+            timestamp: current_clock()?.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn terminate_ticket(ctx: Context<TerminateTicket>) -> Result<()> {
+        let clock = current_clock()?;
+
+        if ctx.accounts.ticket.is_terminated == true {
+            return Err(ErrorCode::TicketTerminated.into());
+        }
+
+        ctx.accounts.ticket.is_terminated = true;
+        ctx.accounts.ticket.terminated_at = clock.unix_timestamp as u64;
+
+        emit!(TicketTerminated {
+            ticket: ctx.accounts.ticket.key(),
+            timestamp: ctx.accounts.ticket.terminated_at,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn accelerate(ctx: Context<Accelerate>, bps: u16) -> Result<()> {
+        if bps == 0 || bps > ctx.accounts.ticket.acceleration_bps {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+
+        // Single trigger: change of control alone. Double trigger: change of control + termination.
+        if ctx.accounts.vestor.change_of_control == false {
+            return Err(ErrorCode::AccelerationNotTriggered.into());
+        }
+        if ctx.accounts.ticket.double_trigger == true && ctx.accounts.ticket.is_terminated == false {
+            return Err(ErrorCode::AccelerationNotTriggered.into());
+        }
+
+        let now = current_clock()?.unix_timestamp;
+        let unvested = ctx.accounts.ticket.amount.checked_sub(ctx.accounts.ticket.schedule().vested(now)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let accelerated = ((unvested as u128).checked_mul(bps as u128)
+            .ok_or(ErrorCode::MathOverflow)? / 10000) as u64;
+
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.accelerated = ticket.accelerated.checked_add(accelerated).ok_or(ErrorCode::MathOverflow)?;
+        // Each basis point of the granted acceleration can only be used once
+        ticket.acceleration_bps -= bps;
+
+        emit!(TicketAccelerated {
+            ticket: ctx.accounts.ticket.key(),
+            bps,
+            amount: accelerated,
+            accelerated: ctx.accounts.ticket.accelerated,
+            timestamp: current_clock()?.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    // Read-only view for wallets: fetch the status with `simulateTransaction` and decode the
+    // `VestingStatus` from the transaction's return data. No account is written.
+    // USD-denominated tickets report what is owed in `usd_claimable` rather than `claimable`.
+    pub fn get_vesting_status(ctx: Context<GetVestingStatus>) -> Result<()> {
+        let clock = current_clock()?;
+        let status = ctx.accounts.ticket.schedule().status(clock.unix_timestamp)?;
+
+        set_return_data(&status.try_to_vec()?);
+
+        Ok(())
+    }
+
+
+    // Stops the vesting clock, e.g. for a leave of absence. Irrevocable tickets cannot be
+    // paused: withholding vesting would take back part of a grant promised in full.
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn pause_ticket(ctx: Context<PauseTicket>) -> Result<()> {
+        let clock = current_clock()?;
+
+        if ctx.accounts.ticket.irrevocable == true {
+            return Err(ErrorCode::TicketIrrevocable.into());
+        }
+
+        let mut schedule = ctx.accounts.ticket.schedule();
+        schedule.pause(clock.unix_timestamp)?;
+        ctx.accounts.ticket.set_ledger(&schedule);
+
+        emit!(TicketPaused {
+            ticket: ctx.accounts.ticket.key(),
+            timestamp: ctx.accounts.ticket.paused_at,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    #[access_control(not_revoked(&ctx.accounts.ticket))]
+    pub fn resume_ticket(ctx: Context<PauseTicket>) -> Result<()> {
+        let clock = current_clock()?;
+
+        // Pushes the end of the schedule out by the length of this pause
+        let mut schedule = ctx.accounts.ticket.schedule();
+        let paused_for = schedule.resume(clock.unix_timestamp)? as u64;
+        ctx.accounts.ticket.set_ledger(&schedule);
+
+        emit!(TicketResumed {
+            ticket: ctx.accounts.ticket.key(),
+            paused_for,
+            total_paused: ctx.accounts.ticket.total_paused,
+            timestamp: clock.unix_timestamp as u64,
+            balance: ctx.accounts.ticket.balance,
+        });
+
+        Ok(())
+    }
+
+
+    // A vesting pool for a large set of recipients: one root over every (index, claimant, amount)
+    // allocation (see `merkle::leaf`) and one schedule they all vest on from now.
+    pub fn create_merkle_pool(
+        ctx: Context<CreateMerklePool>,
+        root: [u8; 32],
+        total_amount: u64,
+        cliff: u64,
+        vesting: u64,
+        bump: u8,
+    ) -> Result<()> {
+        let clock = current_clock()?;
+
+        validate_terms(total_amount, cliff, vesting)?;
+        require!(ctx.accounts.authority_deposit_token_vault.amount >= total_amount, ErrorCode::NotEnoughTokens);
+
+        token::transfer(ctx.accounts.into_transfer_to_vault_context(), total_amount)?;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.vestor = ctx.accounts.vestor.key();
+        pool.authority = ctx.accounts.authority.key();
+        pool.token_mint = ctx.accounts.token_mint.key();
+        pool.vault = ctx.accounts.vault.key();
+        pool.root = root;
+        pool.created_at = clock.unix_timestamp as u64;
+        pool.cliff = cliff;
+        pool.vesting = vesting;
+        pool.total_amount = total_amount;
+        pool.claimed = 0;
+        pool.bump = bump;
+
+        emit!(MerklePoolCreated {
+            pool: ctx.accounts.pool.key(),
+            vestor: ctx.accounts.pool.vestor,
+            root,
+            total_amount,
+            cliff,
+            vesting,
+            timestamp: ctx.accounts.pool.created_at,
+        });
+
+        Ok(())
+    }
+
+
+    // Pays out what a pool allocation has vested so far. The first claim proves the allocation
+    // and creates its position; every claim re-checks the proof against the pool's root.
+    pub fn claim_from_pool(ctx: Context<ClaimFromPool>, index: u64, amount: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        let clock = current_clock()?;
+
+        let leaf = merkle::leaf(index, ctx.accounts.claimant.key, amount);
+        require!(merkle::verify(&proof, &ctx.accounts.pool.root, leaf), ErrorCode::InvalidMerkleProof);
+
+        let position = &mut ctx.accounts.position;
+        if position.pool == Pubkey::default() {
+            position.pool = ctx.accounts.pool.key();
+            position.index = index;
+            position.claimant = ctx.accounts.claimant.key();
+            position.amount = amount;
+            position.claimed = 0;
+        }
+
+        let mut schedule = ctx.accounts.pool.schedule(&ctx.accounts.position);
+        let payout = schedule.claim(clock.unix_timestamp)?;
+
+        let pool_key = ctx.accounts.pool.key();
+        let seeds = &[MERKLE_POOL_SEED, pool_key.as_ref(), &[ctx.accounts.pool.bump]];
+        token::transfer(ctx.accounts
+            .into_transfer_to_claimant_context()
+            .with_signer(&[&seeds[..]]),
+            payout)?;
+
+        ctx.accounts.position.claimed = schedule.claimed;
+        ctx.accounts.pool.claimed = ctx.accounts.pool.claimed.checked_add(payout)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(PoolClaimed {
+            pool: pool_key,
+            index,
+            claimant: ctx.accounts.claimant.key(),
+            amount: payout,
+            claimed: ctx.accounts.position.claimed,
+            timestamp: clock.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // Returns whatever is left in a pool's vault to its authority and closes the pool: the
+    // unclaimed allocations and any excess over the sum of the leaves. Only possible once
+    // everything has vested and claimants have had `MERKLE_POOL_CLAWBACK_GRACE` to claim it.
+    pub fn close_merkle_pool(ctx: Context<CloseMerklePool>) -> Result<()> {
+        let clock = current_clock()?;
+
+        require!(
+            clock.unix_timestamp as u64 >= ctx.accounts.pool.closable_at()?,
+            ErrorCode::MerklePoolStillClaimable
+        );
+
+        let returned = ctx.accounts.vault.amount;
+        let pool_key = ctx.accounts.pool.key();
+        let seeds = &[MERKLE_POOL_SEED, pool_key.as_ref(), &[ctx.accounts.pool.bump]];
+        token::transfer(ctx.accounts
+            .into_transfer_to_authority_context()
+            .with_signer(&[&seeds[..]]),
+            returned)?;
+
+        // The emptied vault goes too, its rent back to the authority along with the pool's
+        token::close_account(ctx.accounts
+            .into_close_vault_context()
+            .with_signer(&[&seeds[..]]))?;
+
+        emit!(MerklePoolClosed {
+            pool: pool_key,
+            authority: ctx.accounts.authority.key(),
+            returned,
+            claimed: ctx.accounts.pool.claimed,
+            timestamp: clock.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // One vault split among several claimants by basis points. Every claimant vests their
+    // allocation on the same schedule and claims it independently.
+    pub fn create_shared_ticket(
+        ctx: Context<CreateSharedTicket>,
+        shares: Vec<Share>,
+        cliff: u64,
+        vesting: u64,
+        amount: u64,
+        irrevocable: bool,
+        bump: u8,
+    ) -> Result<()> {
+        let clock = current_clock()?;
+
+        validate_terms(amount, cliff, vesting)?;
+        require!(shares.len() <= MAX_SHARED_CLAIMANTS, ErrorCode::TooManySharedClaimants);
+        for (i, share) in shares.iter().enumerate() {
+            require!(
+                !shares[..i].iter().any(|other| other.claimant == share.claimant),
+                ErrorCode::DuplicateSharedClaimant
+            );
+        }
+        let shares_bps: Vec<u16> = shares.iter().map(|share| share.share_bps).collect();
+        let allocations = split_by_shares(amount, &shares_bps)?;
+
+        require!(ctx.accounts.ticket_creator_deposit_token_vault.amount >= amount, ErrorCode::NotEnoughTokens);
+
+        token::transfer(ctx.accounts.into_transfer_to_vault_context(), amount)?;
+
+        let shared_ticket = &mut ctx.accounts.shared_ticket;
+        shared_ticket.vestor = ctx.accounts.vestor.key();
+        shared_ticket.owner = ctx.accounts.owner.key();
+        shared_ticket.token_mint = ctx.accounts.token_mint.key();
+        shared_ticket.vault = ctx.accounts.vault.key();
+        shared_ticket.creator_deposit_token_vault = ctx.accounts.ticket_creator_deposit_token_vault.key();
+        shared_ticket.cliff = cliff;
+        shared_ticket.vesting = vesting;
+        shared_ticket.amount = amount;
+        shared_ticket.created_at = clock.unix_timestamp as u64;
+        shared_ticket.irrevocable = irrevocable;
+        shared_ticket.bump = bump;
+        shared_ticket.beneficiaries = shares
+            .iter()
+            .zip(allocations)
+            .map(|(share, allocation)| Beneficiary {
+                claimant: share.claimant,
+                share_bps: share.share_bps,
+                allocation,
+                claimed: 0,
+            })
+            .collect();
+
+        emit!(SharedTicketCreated {
+            shared_ticket: ctx.accounts.shared_ticket.key(),
+            vestor: ctx.accounts.shared_ticket.vestor,
+            shares,
+            amount,
+            cliff,
+            vesting,
+            irrevocable,
+            timestamp: ctx.accounts.shared_ticket.created_at,
+        });
+
+        Ok(())
+    }
+
+
+    // Pays the signing claimant what their share has vested so far. After revocation a
+    // share keeps vesting up to `revoked_at` and no further.
+    pub fn claim_shared(ctx: Context<ClaimShared>) -> Result<()> {
+        let clock = current_clock()?;
+
+        let shared_ticket = &ctx.accounts.shared_ticket;
+        let index = shared_ticket
+            .beneficiaries
+            .iter()
+            .position(|beneficiary| beneficiary.claimant == ctx.accounts.claimant.key())
+            .ok_or(ErrorCode::NotASharedTicketClaimant)?;
+
+        let mut schedule = shared_ticket.schedule(&shared_ticket.beneficiaries[index]);
+        let payout = schedule.claim(shared_ticket.vesting_clock(clock.unix_timestamp))?;
+
+        let shared_ticket_key = shared_ticket.key();
+        let seeds = &[SHARED_TICKET_SEED, shared_ticket_key.as_ref(), &[shared_ticket.bump]];
+        token::transfer(ctx.accounts
+            .into_transfer_to_claimant_context()
+            .with_signer(&[&seeds[..]]),
+            payout)?;
+
+        ctx.accounts.shared_ticket.beneficiaries[index].claimed = schedule.claimed;
+
+        emit!(SharedTicketClaimed {
+            shared_ticket: shared_ticket_key,
+            claimant: ctx.accounts.claimant.key(),
+            amount: payout,
+            claimed: schedule.claimed,
+            timestamp: clock.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+
+    // Returns every share's unvested remainder to the creator. What each claimant had
+    // vested by now stays in the vault for them to claim.
+    pub fn revoke_shared(ctx: Context<RevokeShared>) -> Result<()> {
+        let clock = current_clock()?;
+        let shared_ticket = &ctx.accounts.shared_ticket;
+
+        if shared_ticket.is_revoked {
+            return Err(ErrorCode::TicketRevoked.into());
+        }
+        if shared_ticket.irrevocable {
+            return Err(ErrorCode::TicketIrrevocable.into());
+        }
+
+        let mut returned: u64 = 0;
+        for beneficiary in shared_ticket.beneficiaries.iter() {
+            let vested = shared_ticket.schedule(beneficiary).vested(clock.unix_timestamp)?;
+            returned = returned
+                .checked_add(beneficiary.allocation.saturating_sub(vested))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let shared_ticket_key = shared_ticket.key();
+        let seeds = &[SHARED_TICKET_SEED, shared_ticket_key.as_ref(), &[shared_ticket.bump]];
+        token::transfer(ctx.accounts
+            .into_transfer_to_ticket_creator_context()
+            .with_signer(&[&seeds[..]]),
+            returned)?;
+
+        ctx.accounts.shared_ticket.is_revoked = true;
+        ctx.accounts.shared_ticket.revoked_at = clock.unix_timestamp as u64;
+
+        emit!(SharedTicketRevoked {
+            shared_ticket: shared_ticket_key,
+            amount: returned,
+            timestamp: clock.unix_timestamp as u64,
+        });
+
+        Ok(())
+    }
+
+  
+}
+
+
+
+
+
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    // Total 7 accounts used in Initialization of the Program :
+    // vestor being created
+   #[account(init, payer = owner, space = 8 + 8 + 32 + 8 + 32 + 8 + 8 + 8 + 8 + 160 + 2)]
+    pub vestor : Box<Account<'info, Vestor>>,
+
+    #[account(mut, has_one = owner, constraint = contract_owner_deposit_token_vault.mint == token_mint.key())]
+    pub contract_owner_deposit_token_vault : Box<Account<'info, TokenAccount>>, 
+
+    #[account(mut,
+        constraint = ticket_creator_deposit_token_vault.mint == contract_owner_deposit_token_vault.mint)]
+    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_mint : Box<Account<'info, Mint>>, 
+
+    // The Owner of contract_owner_deposit_token_vault
+    #[account(mut)]
+    pub owner : Signer<'info>,
+    
+    pub token_program : Program<'info, Token>, 
+
+    pub system_program : Program<'info, System>, 
+
+     
+}
+
+    impl<'info> Initialize<'info> {
+        pub fn has_access(_ctx: &Context<Initialize>) -> Result<()> {
+         // TODO : add some whitelist admins
+         
+          Ok(())
+        }
+      } 
+
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey, cliff: u64, vesting: u64, amount: u64, irrevocable: bool, bump: u8)]
+pub struct CreateTicket<'info> {
+    // Total 10 accounts used in 'Create'
+    //ticket being created
+    #[account(zero)]
+    pub ticket : Box<Account<'info, Ticket>>,
+
+    // The Owner of ticket_creator_deposit_token_vault, who signs the transfer of the grant
+    pub owner : Signer<'info>,
+
+    ///CHECK: Program Derived address (PDA) for the Ticket
+    // The ticket is still zeroed here, so the bump comes from the instruction
+    #[account(
+        seeds = [
+            ticket.to_account_info().key.as_ref(),
+            vestor.tickets_issued.to_string().as_ref()
+            ],
+        bump = bump,
+    )]
+    pub signer : AccountInfo<'info>, 
+
+    pub token_mint : Box<Account<'info, Mint>>, 
+
+    // This is the 'from' token
+    #[account(mut, has_one = owner )]
+    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    //This is the 'to' token
+    #[account(mut, 
+        constraint = claimant_receive_token_vault.mint == ticket_creator_deposit_token_vault.mint)]
+    pub claimant_receive_token_vault : Box<Account<'info, TokenAccount>>, 
+    
+    // Ticket's token vault owned by the 'signer PDA'. This is the intermediate/temp token account. 
+    #[account(mut, 
+        constraint = &vault.owner == signer.key)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    
+    /// CHECK : The Token program
+    pub token_program : AccountInfo<'info>, 
+
+    // Writable so the ticket count can advance
+    #[account(mut)]
+    pub vestor : Box<Account<'info, Vestor>>,
+
+    /// CHECK : the System Program 
+    pub system_program : AccountInfo<'info>, 
+
+
+    }
+
+    impl<'info> CreateTicket<'info> {
+        pub fn accounts(ctx: &Context<CreateTicket>, bump: u8) -> Result<()> {
+       
+            let signer_account = Pubkey::create_program_address(
+                &[ctx.accounts.ticket.to_account_info().key.as_ref(), 
+                ctx.accounts.vestor.tickets_issued.to_string().as_ref(), 
+                &[bump]], 
+                &ctx.program_id)
+                .map_err(|_| ErrorCode::InvalidNonce)?;
+  
+            if &signer_account != ctx.accounts.signer.to_account_info().key {
+                return Err(ErrorCode::InvalidProgramInitializer.into());
+            }
+  
+          Ok(())
+        }
+
+      } 
+
+
+   
+#[derive(Accounts)]
+pub struct Claim<'info> {  
+    // Total 17 accounts are used for 'Claim'
+    
+    /// CHECK: The 'signer PDA' is not dangerous because of seed + bump contraints
+    #[account(
+        seeds = [
+            ticket.to_account_info().key.as_ref(),
+            ticket.signer_seed.to_string().as_ref()
+            ],
+        bump = ticket.bump,
+    )]
+    pub signer : AccountInfo<'info>, 
+
+    #[account(
+        mut,
+        has_one = claimant,
+        has_one = claimant_receive_token_vault, 
+        has_one = vestor,
+        has_one = token_mint,
+        constraint = ticket.balance > 0,
+        constraint = ticket.balance <= pda_deposit_token_vault.amount,
+        constraint = ticket.vault == *pda_deposit_token_vault.to_account_info().key, 
+    )]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub vestor : Box<Account<'info, Vestor>>,
+
+    pub token_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = pda_deposit_token_vault.owner == signer.key(),
+    )]
+    pub pda_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = claimant_receive_token_vault.mint == pda_deposit_token_vault.mint,
+        constraint = claimant_receive_token_vault.owner == claimant.key(),
+    )]
+    pub claimant_receive_token_vault: Box<Account<'info, TokenAccount>>,
+
+    ///CHECK: The claimant is not unsafe because some other constraints have been issued to Ticket
+    /// which ensure that ticket.claimant == *claimant.key (see => has_one = claimant)
+    /// The claimant pays rent the first time a vestor's ChainlinkValue PDAs are created
+    #[account(mut, signer)]
+    pub claimant: AccountInfo<'info>,
+
+    /// CHECK : Source of the early-release draw, must be the feed registered on the vestor.
+    /// Vestors without a feed pass the default key (the system program) and skip the draw.
+    #[account(constraint = randomness_feed.key() == vestor.randomness_feed)]
+    pub randomness_feed: AccountInfo<'info>,
+
+    ///CHECK : This account just reads the Sol Price from SOLANA_FEED ADDRESS && which arrived from the Chainlink Program.
+    /// Must be the SOL feed registered on the vestor and owned by the registered Chainlink program
+    /// (or, in `mock-oracle` builds, by this program for a `MockPriceFeed`)
+    #[account(
+        constraint = chainlink_sol_feed.key() == vestor.oracles.chainlink_sol_feed @ ErrorCode::InvalidOracleFeed,
+        constraint = is_allowed_feed_owner(chainlink_sol_feed.owner, program_id, &vestor) @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub chainlink_sol_feed: AccountInfo<'info>,
+
+    ///CHECK : This account just reads the ETH Price from ETHEREUM_FEED ADDRESS && which arrived from the Chainlink Program.
+    /// Must be the ETH feed registered on the vestor and owned by the registered Chainlink program
+    /// (or, in `mock-oracle` builds, by this program for a `MockPriceFeed`)
+    #[account(
+        constraint = chainlink_eth_feed.key() == vestor.oracles.chainlink_eth_feed @ ErrorCode::InvalidOracleFeed,
+        constraint = is_allowed_feed_owner(chainlink_eth_feed.owner, program_id, &vestor) @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub chainlink_eth_feed: AccountInfo<'info>,
+
+    /// CHECK : This is the Chainlink program's account, must be the one registered on the vestor
+    #[account(address = vestor.oracles.chainlink_program @ ErrorCode::InvalidOracleProgram)]
+    pub chainlink_program: AccountInfo<'info>,
+
+    /// CHECK : This is the Pyth SOL price account, must be the one registered on the vestor
+    /// and owned by the registered Pyth program
+    #[account(
+        constraint = pyth_account.key() == vestor.oracles.pyth_sol_price @ ErrorCode::InvalidOracleFeed,
+        constraint = *pyth_account.owner == vestor.oracles.pyth_program @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub pyth_account : AccountInfo<'info>,
+
+    /// CHECK : Feed a USD grant is priced at, the one `set_usd_terms` stored on the ticket.
+    /// Token grants pass the default key (the system program) and never read it.
+    #[account(constraint = usd_price_feed.key() == ticket.usd_price_feed @ ErrorCode::InvalidOracleFeed)]
+    pub usd_price_feed: AccountInfo<'info>,
+
+     /// CHECK : System Program address is already defined
+     #[account(address = system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+
+    // Latest SOL answer for this vestor, created on the first claim and updated in place after that
+    #[account(
+        init_if_needed,
+        payer = claimant,
+        space = 8 + 16 + 4 + 8,
+        seeds = [CHAINLINK_VALUE_SEED, vestor.key().as_ref(), chainlink_sol_feed.key().as_ref()],
+        bump,
+    )]
+    pub chainlink_sol_value: Box<Account<'info, ChainlinkValue>>,
+
+    // Latest ETH answer for this vestor, kept apart so it no longer clobbers the SOL value
+    #[account(
+        init_if_needed,
+        payer = claimant,
+        space = 8 + 16 + 4 + 8,
+        seeds = [CHAINLINK_VALUE_SEED, vestor.key().as_ref(), chainlink_eth_feed.key().as_ref()],
+        bump,
+    )]
+    pub chainlink_eth_value: Box<Account<'info, ChainlinkValue>>,
+
+    //pub system_program: Program<'info, Token>,
+    pub token_program: Program<'info, Token>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct Revoke<'info> {
+    //Total 8 accounts used for Revoke
+
+    /// CHECK: The 'signer PDA' is not dangerous because of seed + bump contraints
+    #[account(
+        seeds = [
+            ticket.to_account_info().key.as_ref(),
+            ticket.signer_seed.to_string().as_ref()
+            ],
+        bump = ticket.bump,
+    )]
+    pub signer : AccountInfo<'info>, 
+
+    pub vestor : Box<Account<'info, Vestor>>,
+
+    // Only the ticket's owner can revoke it, and only into the account it was funded from
+    #[account(mut, address = ticket.owner)]
+    pub ticket_creator: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = vestor,
         has_one = token_mint,
         constraint = ticket.vault == *pda_deposit_token_vault.to_account_info().key,
         constraint = ticket.balance > 0,
@@ -518,67 +1630,1025 @@ pub struct Revoke<'info> {
 
     pub token_mint: Box<Account<'info, Mint>>,
 
+    #[account(mut, address = ticket.creator_deposit_token_vault)]
+    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = pda_deposit_token_vault.mint == token_mint.key(),
+        constraint = pda_deposit_token_vault.owner == signer.key(),
+    )]
+    pub pda_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+
+    pub token_program: Program<'info, Token>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct InitRandomnessFeed<'info> {
+    // Total 5 accounts used for InitRandomnessFeed
+
+    #[account(init, payer = authority, space = 8 + 32 + 8 + 32 + 8)]
+    pub randomness_feed: Box<Account<'info, RandomnessFeed>>,
+
+    #[account(mut, has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    /// CHECK : The key allowed to publish randomness, e.g. a VRF oracle's signer
+    pub randomness_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct PublishRandomness<'info> {
+    // Total 2 accounts used for PublishRandomness
+
+    #[account(mut, constraint = randomness_feed.authority == randomness_authority.key() @ ErrorCode::InvalidRandomnessAuthority)]
+    pub randomness_feed: Box<Account<'info, RandomnessFeed>>,
+
+    pub randomness_authority: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct RequestEarlyRelease<'info> {
+    // Total 4 accounts used for RequestEarlyRelease
+
+    #[account(mut, has_one = claimant, has_one = vestor)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(constraint = randomness_feed.key() == vestor.randomness_feed)]
+    pub randomness_feed: Box<Account<'info, RandomnessFeed>>,
+
+    pub claimant: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct AddPriceTranche<'info> {
+    // Total 4 accounts used for AddPriceTranche
+
+    #[account(init, payer = owner, space = 8 + 32 + 32 + 16 + 4 + 2 + 8 + 8 + 8 + 1)]
+    pub price_tranche: Box<Account<'info, PriceTranche>>,
+
+    #[account(mut, has_one = owner)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct CheckPriceTranche<'info> {
+    // Total 5 accounts used for CheckPriceTranche
+
+    #[account(mut, has_one = ticket)]
+    pub price_tranche: Box<Account<'info, PriceTranche>>,
+
+    #[account(mut, has_one = vestor)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    ///CHECK : Must be the feed configured on the tranche, owned by the registered Chainlink program
+    /// (or, in `mock-oracle` builds, by this program for a `MockPriceFeed`)
+    #[account(
+        constraint = price_feed.key() == price_tranche.feed @ ErrorCode::InvalidOracleFeed,
+        constraint = is_allowed_feed_owner(price_feed.owner, program_id, &vestor) @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK : This is the Chainlink program's account, must be the one registered on the vestor
+    #[account(address = vestor.oracles.chainlink_program @ ErrorCode::InvalidOracleProgram)]
+    pub chainlink_program: AccountInfo<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct CheckPriceTrancheTwap<'info> {
+    // Total 4 accounts used for CheckPriceTrancheTwap
+
+    #[account(mut, has_one = ticket)]
+    pub price_tranche: Box<Account<'info, PriceTranche>>,
+
+    #[account(mut, has_one = vestor)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(
+        has_one = vestor,
+        constraint = price_history.feed == price_tranche.feed @ ErrorCode::InvalidOracleFeed,
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct InitPriceHistory<'info> {
+    // Total 4 accounts used for InitPriceHistory
+
+    #[account(init, payer = authority, space = 8 + 32 + 32 + 4 + 8 + 2 + 2 + 4 + PRICE_HISTORY_LEN * (16 + 8))]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    #[account(has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct RecordPrice<'info> {
+    // Total 4 accounts used for RecordPrice
+
+    #[account(mut, has_one = vestor)]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    ///CHECK : Must be the history's feed, owned by the registered Chainlink program
+    /// (or, in `mock-oracle` builds, by this program for a `MockPriceFeed`)
+    #[account(
+        constraint = price_feed.key() == price_history.feed @ ErrorCode::InvalidOracleFeed,
+        constraint = is_allowed_feed_owner(price_feed.owner, program_id, &vestor) @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub price_feed: AccountInfo<'info>,
+
+    /// CHECK : This is the Chainlink program's account, must be the one registered on the vestor
+    #[account(address = vestor.oracles.chainlink_program @ ErrorCode::InvalidOracleProgram)]
+    pub chainlink_program: AccountInfo<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct AmendSchedule<'info> {
+    // Total 3 accounts used for AmendSchedule
+
+    // A schedule change needs the consent of both the ticket owner and the claimant
+    #[account(mut, has_one = owner, has_one = claimant)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub owner: Signer<'info>,
+
+    pub claimant: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct SetUsdTerms<'info> {
+    // Total 4 accounts used for SetUsdTerms
+
+    // Converting a grant to USD terms needs the consent of both the ticket owner and the claimant
+    #[account(mut, has_one = owner, has_one = claimant)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    /// CHECK : The vestor's `MintPriceFeed` for the ticket's mint; the handler rejects it if
+    /// the vestor never registered one
+    #[account(seeds = [MINT_PRICE_FEED_SEED, ticket.vestor.as_ref(), ticket.token_mint.as_ref()], bump)]
+    pub mint_price_feed: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub claimant: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct SetMintPriceFeed<'info> {
+    // Total 6 accounts used for SetMintPriceFeed
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + 32 + 32 + 32,
+        seeds = [MINT_PRICE_FEED_SEED, vestor.key().as_ref(), token_mint.key().as_ref()],
+        bump,
+    )]
+    pub mint_price_feed: Box<Account<'info, MintPriceFeed>>,
+
+    #[account(has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    pub token_mint: Box<Account<'info, Mint>>,
+
+    ///CHECK : Must be owned by the registered Chainlink program (or, in `mock-oracle` builds,
+    /// by this program for a `MockPriceFeed`)
+    #[account(
+        constraint = is_allowed_feed_owner(price_feed.owner, program_id, &vestor) @ ErrorCode::InvalidOracleProgram,
+    )]
+    pub price_feed: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct SetAcceleration<'info> {
+    // Total 2 accounts used for SetAcceleration
+
+    #[account(mut, has_one = owner)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub owner: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct TerminateTicket<'info> {
+    // Total 2 accounts used for TerminateTicket
+
+    // Ending the claimant's service is the ticket owner's call alone
+    #[account(mut, has_one = owner)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub owner: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct SetChangeOfControl<'info> {
+    // Total 2 accounts used for SetChangeOfControl
+
+    #[account(mut, has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    pub authority: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct ConfigureVestor<'info> {
+    // Total 2 accounts used for the vestor's admin settings
+
+    #[account(mut, has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    pub authority: Signer<'info>,
+}
+
+
+
+
+#[cfg(feature = "mock-oracle")]
+#[derive(Accounts)]
+pub struct InitMockPriceFeed<'info> {
+    // Total 4 accounts used for InitMockPriceFeed
+
+    #[account(init, payer = authority, space = 8 + 32 + 16 + 4 + 8)]
+    pub mock_price_feed: Box<Account<'info, MockPriceFeed>>,
+
+    #[account(has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[cfg(feature = "mock-oracle")]
+#[derive(Accounts)]
+pub struct SetMockPrice<'info> {
+    // Total 3 accounts used for SetMockPrice
+
+    #[account(mut, has_one = vestor)]
+    pub mock_price_feed: Box<Account<'info, MockPriceFeed>>,
+
+    #[account(has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    pub authority: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct Accelerate<'info> {
+    // Total 3 accounts used for Accelerate
+
+    #[account(mut, has_one = owner, has_one = vestor)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    pub owner: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct GetVestingStatus<'info> {
+    // Total 1 account used for GetVestingStatus
+
+    pub ticket: Box<Account<'info, Ticket>>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct PauseTicket<'info> {
+    // Total 2 accounts used for Pause/Resume
+
+    // Only the revocation authority (the ticket owner) may pause or resume vesting
+    #[account(mut, has_one = owner)]
+    pub ticket: Box<Account<'info, Ticket>>,
+
+    pub owner: Signer<'info>,
+}
+
+
+
+
+#[derive(Accounts)]
+#[instruction(root: [u8; 32], total_amount: u64, cliff: u64, vesting: u64, bump: u8)]
+pub struct CreateMerklePool<'info> {
+    // Total 9 accounts used for CreateMerklePool
+
+    #[account(init, payer = authority, space = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1)]
+    pub pool: Box<Account<'info, MerklePool>>,
+
+    /// CHECK : PDA that owns the pool's vault
+    #[account(seeds = [MERKLE_POOL_SEED, pool.key().as_ref()], bump = bump)]
+    pub pool_signer: AccountInfo<'info>,
+
+    #[account(has_one = authority)]
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut,
+        constraint = authority_deposit_token_vault.owner == authority.key(),
+        constraint = authority_deposit_token_vault.mint == token_mint.key())]
+    pub authority_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = vault.owner == pool_signer.key(),
+        constraint = vault.mint == token_mint.key())]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ClaimFromPool<'info> {
+    // Total 8 accounts used for ClaimFromPool
+
+    #[account(mut, has_one = vault)]
+    pub pool: Box<Account<'info, MerklePool>>,
+
+    /// CHECK : PDA that owns the pool's vault
+    #[account(seeds = [MERKLE_POOL_SEED, pool.key().as_ref()], bump = pool.bump)]
+    pub pool_signer: AccountInfo<'info>,
+
+    // Created by the allocation's first claim
+    #[account(
+        init_if_needed,
+        payer = claimant,
+        space = 8 + 32 + 8 + 32 + 8 + 8,
+        seeds = [MERKLE_POSITION_SEED, pool.key().as_ref(), &index.to_le_bytes()],
+        bump,
+    )]
+    pub position: Box<Account<'info, PoolPosition>>,
+
     #[account(mut)]
-    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+    pub claimant: Signer<'info>,
+
+    #[account(mut,
+        constraint = claimant_token_account.owner == claimant.key(),
+        constraint = claimant_token_account.mint == pool.token_mint)]
+    pub claimant_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct CloseMerklePool<'info> {
+    // Total 6 accounts used for CloseMerklePool
+
+    #[account(mut, has_one = authority, has_one = vault, close = authority)]
+    pub pool: Box<Account<'info, MerklePool>>,
+
+    /// CHECK : PDA that owns the pool's vault
+    #[account(seeds = [MERKLE_POOL_SEED, pool.key().as_ref()], bump = pool.bump)]
+    pub pool_signer: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut,
+        constraint = authority_token_account.owner == authority.key(),
+        constraint = authority_token_account.mint == pool.token_mint)]
+    pub authority_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+
+
+
+#[derive(Accounts)]
+#[instruction(shares: Vec<Share>, cliff: u64, vesting: u64, amount: u64, irrevocable: bool, bump: u8)]
+pub struct CreateSharedTicket<'info> {
+    // Total 9 accounts used for CreateSharedTicket
 
     #[account(
-        constraint = pda_deposit_token_vault.mint == token_mint.key(),
-        constraint = pda_deposit_token_vault.owner == signer.key(),
+        init,
+        payer = owner,
+        space = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 1
+            + 4 + MAX_SHARED_CLAIMANTS * (32 + 2 + 8 + 8),
     )]
-    pub pda_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+    pub shared_ticket: Box<Account<'info, SharedTicket>>,
+
+    /// CHECK : PDA that owns the shared ticket's vault
+    #[account(seeds = [SHARED_TICKET_SEED, shared_ticket.key().as_ref()], bump = bump)]
+    pub signer: AccountInfo<'info>,
+
+    pub vestor: Box<Account<'info, Vestor>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub token_mint: Box<Account<'info, Mint>>,
+
+    #[account(mut,
+        constraint = ticket_creator_deposit_token_vault.owner == owner.key(),
+        constraint = ticket_creator_deposit_token_vault.mint == token_mint.key())]
+    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+        constraint = vault.owner == signer.key(),
+        constraint = vault.mint == token_mint.key())]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct ClaimShared<'info> {
+    // Total 6 accounts used for ClaimShared
+
+    #[account(mut, has_one = vault)]
+    pub shared_ticket: Box<Account<'info, SharedTicket>>,
+
+    /// CHECK : PDA that owns the shared ticket's vault
+    #[account(seeds = [SHARED_TICKET_SEED, shared_ticket.key().as_ref()], bump = shared_ticket.bump)]
+    pub signer: AccountInfo<'info>,
+
+    pub claimant: Signer<'info>,
+
+    #[account(mut,
+        constraint = claimant_token_account.owner == claimant.key(),
+        constraint = claimant_token_account.mint == shared_ticket.token_mint)]
+    pub claimant_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+
+
+
+#[derive(Accounts)]
+pub struct RevokeShared<'info> {
+    // Total 6 accounts used for RevokeShared
+
+    // Only the owner can revoke, and only into the account the ticket was funded from
+    #[account(mut, has_one = owner, has_one = vault)]
+    pub shared_ticket: Box<Account<'info, SharedTicket>>,
+
+    /// CHECK : PDA that owns the shared ticket's vault
+    #[account(seeds = [SHARED_TICKET_SEED, shared_ticket.key().as_ref()], bump = shared_ticket.bump)]
+    pub signer: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut, address = shared_ticket.creator_deposit_token_vault)]
+    pub ticket_creator_deposit_token_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+
+
+
+#[account]
+#[derive(Default)]
+pub struct Vestor {
+   
+    tickets_issued: u8, // 8
+    pub authority: Pubkey, // 32
+    pub change_of_control: bool, // 8
+    pub randomness_feed: Pubkey, // 32
+    pub max_oracle_age: u64, // 8
+    pub pyth_max_conf_bps: u64, // 8
+    pub pyth_max_slot_age: u64, // 8
+    pub max_oracle_deviation_bps: u64, // 8
+    pub oracles: OracleRegistry, // 160
+    pub price_policy: PricePolicy, // 2
+}
+
+
+
+// Oracle programs and feeds a vestor accepts. Every oracle account passed to the
+// program is checked against this, so a claimant cannot substitute a fake feed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct OracleRegistry {
+    pub chainlink_program: Pubkey, // 32
+    pub chainlink_sol_feed: Pubkey, // 32
+    pub chainlink_eth_feed: Pubkey, // 32
+    pub pyth_program: Pubkey, // 32
+    pub pyth_sol_price: Pubkey, // 32
+}
+
+impl OracleRegistry {
+    // The devnet Chainlink and Pyth accounts the program read before vestors had a registry
+    pub fn devnet() -> Self {
+        OracleRegistry {
+            chainlink_program: pubkey!("CaH12fwNTKJAG8PxEvo9R96Zc2j8qNHZaFj8ZW49yZNT"),
+            chainlink_sol_feed: pubkey!("EdWr4ww1Dq82vPe8GFjjcVPo2Qno3Nhn6baCgM3dCy28"),
+            chainlink_eth_feed: pubkey!("5zxs8888az8dgB5KauGEFoPuMANtrKtkpFiFRmo3cSa9"),
+            pyth_program: pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s"),
+            pyth_sol_price: pubkey!("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix"),
+        }
+    }
+}
+
+
+
+#[account]
+#[derive(Default)]
+pub struct Ticket {
+   pub token_mint : Pubkey, // 32
+    pub vestor: Pubkey, // 32
+    pub owner: Pubkey, // 32
+    pub claimant: Pubkey, //32
+    pub cliff: u64, //8
+    pub vesting: u64, //8
+    pub amount: u64, //8
+    pub claimed: u64, //8
+    pub balance: u64, //8
+    pub created_at: u64, //8
+    pub last_claimed_at: u64, //8
+    pub num_claims: u64, //8
+    pub irrevocable: bool, //8
+    pub is_revoked: bool, //8
+    pub revoked_at: u64, //8
+    pub is_paused: bool, //8
+    pub paused_at: u64, //8
+    pub total_paused: u64, //8
+    pub acceleration_bps: u16, //8
+    pub double_trigger: bool, //8
+    pub is_terminated: bool, //8
+    pub terminated_at: u64, //8
+    pub accelerated: u64, //8
+    pub lottery_round: u64, //8
+    pub milestone_bps: u16, //8
+    pub milestone_unlocked: u64, //8
+    pub usd_amount: u64, //8
+    pub usd_paid: u64, //8
+    pub usd_unpaid: u64, //8
+    pub vault : Pubkey, //32 
+    pub creator_deposit_token_vault : Pubkey, //32
+    pub claimant_receive_token_vault : Pubkey, //32
+    pub bump : u8, // 8
+    // `vestor.tickets_issued` when the ticket was created, the second seed of its signer PDA
+    pub signer_seed : u8, // 8
+    // Feed a USD grant is priced at, the vestor's `MintPriceFeed` for `token_mint` when
+    // `set_usd_terms` ran. The default key for token grants.
+    pub usd_price_feed: Pubkey, // 32
+
+
+}
+
+
+
+// A performance-vesting tranche: releases `release_bps` of the ticket's amount once
+// `feed` has stayed at or above `strike` (scaled by `strike_decimals`) for `hold_seconds`,
+// or, when `twap_window` is set, once its TWAP over that window is at or above the strike.
+#[account]
+pub struct PriceTranche {
+    pub ticket: Pubkey, // 32
+    pub feed: Pubkey, // 32
+    pub strike: i128, // 16
+    pub strike_decimals: u32, // 4
+    pub release_bps: u16, // 2
+    pub hold_seconds: u64, // 8
+    pub twap_window: u64, // 8
+    pub above_since: u64, // 8
+    pub is_released: bool, // 1
+}
+
+
+
+pub const PRICE_HISTORY_LEN: usize = 64;
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PricePoint {
+    pub value: i128, // 16
+    pub timestamp: u64, // 8
+}
+
+
+
+// Ring buffer of the last `PRICE_HISTORY_LEN` prices of one feed, all stored with exponent `expo`.
+// `head` is the slot the next price is written to.
+#[account]
+pub struct PriceHistory {
+    pub vestor: Pubkey, // 32
+    pub feed: Pubkey, // 32
+    pub expo: i32, // 4
+    pub min_interval: u64, // 8
+    pub head: u16, // 2
+    pub len: u16, // 2
+    pub points: Vec<PricePoint>, // 4 + PRICE_HISTORY_LEN * 24
+}
+
+
+
+// Program-owned price feed for local testing, written by the vestor authority via `set_mock_price`
+#[cfg(feature = "mock-oracle")]
+#[account]
+pub struct MockPriceFeed {
+    pub vestor: Pubkey, // 32
+    pub value: i128, // 16
+    pub expo: i32, // 4
+    pub updated_at: u64, // 8
+}
+
+
+
+#[account]
+pub struct RandomnessFeed {
+    pub authority: Pubkey, // 32
+    pub round: u64, // 8
+    pub value: [u8; 32], // 32
+    pub updated_at: u64, // 8
+}
+
 
 
-    pub token_program: Program<'info, Token>,
+// Latest validated answer of one Chainlink feed, kept in a PDA per vestor and feed
+// (see `CHAINLINK_VALUE_SEED`) that every claim updates in place.
+#[account]
+pub struct ChainlinkValue {
+    pub value: i128, // 16
+    pub decimals: u32, // 4
+    pub updated_at: u64, // 8
 }
 
 
 
+pub const CHAINLINK_VALUE_SEED: &[u8] = b"chainlink_value";
+
 
+// The price feed a vestor prices USD grants in one mint at, in a PDA per vestor and mint
+// (see `MINT_PRICE_FEED_SEED`) written by `set_mint_price_feed`.
 #[account]
-pub struct Vestor {
-   
-    tickets_issued: u8, // 8
+pub struct MintPriceFeed {
+    pub vestor: Pubkey, // 32
+    pub token_mint: Pubkey, // 32
+    pub feed: Pubkey, // 32
 }
 
+pub const MINT_PRICE_FEED_SEED: &[u8] = b"mint_price_feed";
+
 
 
+// Vesting pool over a merkle root of allocations. Every allocation vests on the pool's
+// schedule; claimed amounts are tracked per allocation in a `PoolPosition`.
 #[account]
-pub struct Ticket {
-   pub token_mint : Pubkey, // 32
-    pub owner: Pubkey, // 32
-    pub claimant: Pubkey, //32
-    pub cliff: u64, //8
-    pub vesting: u64, //8
-    pub amount: u64, //8
-    pub claimed: u64, //8
-    pub balance: u64, //8
-    pub created_at: u64, //8
-    pub last_claimed_at: u64, //8
-    pub num_claims: u64, //8
-    pub irrevocable: bool, //8
-    pub is_revoked: bool, //8
-    pub revoked_at: u64, //8
-    pub vault : Pubkey, //32 
-    pub creator_deposit_token_vault : Pubkey, //32
-    pub claimant_receive_token_vault : Pubkey, //32
-    pub bump : u8, // 8
-    
+pub struct MerklePool {
+    pub vestor: Pubkey, // 32
+    pub authority: Pubkey, // 32
+    pub token_mint: Pubkey, // 32
+    pub vault: Pubkey, // 32
+    pub root: [u8; 32], // 32
+    pub created_at: u64, // 8
+    pub cliff: u64, // 8
+    pub vesting: u64, // 8
+    pub total_amount: u64, // 8
+    pub claimed: u64, // 8
+    pub bump: u8, // 1
+}
 
+// One allocation's claims, at PDA [MERKLE_POSITION_SEED, pool, index as little-endian u64]
+#[account]
+pub struct PoolPosition {
+    pub pool: Pubkey, // 32
+    pub index: u64, // 8
+    pub claimant: Pubkey, // 32
+    pub amount: u64, // 8
+    pub claimed: u64, // 8
 }
 
 
 
+// A ticket whose vault is split among several claimants, each vesting `allocation`
+// on the ticket's schedule. Its vault is owned by PDA [SHARED_TICKET_SEED, shared_ticket].
 #[account]
-pub struct ChainlinkValue {
-    pub value: i128,
-    pub decimals: u32,
+#[derive(Default)]
+pub struct SharedTicket {
+    pub vestor: Pubkey, // 32
+    pub owner: Pubkey, // 32
+    pub token_mint: Pubkey, // 32
+    pub vault: Pubkey, // 32
+    pub creator_deposit_token_vault: Pubkey, // 32
+    pub cliff: u64, // 8
+    pub vesting: u64, // 8
+    pub amount: u64, // 8
+    pub created_at: u64, // 8
+    pub irrevocable: bool, // 1
+    pub is_revoked: bool, // 1
+    pub revoked_at: u64, // 8
+    pub bump: u8, // 1
+    pub beneficiaries: Vec<Beneficiary>, // 4 + 50 * MAX_SHARED_CLAIMANTS
+}
+
+// A claimant's share of a shared ticket, as passed to `create_shared_ticket`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Share {
+    pub claimant: Pubkey, // 32
+    pub share_bps: u16, // 2
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Beneficiary {
+    pub claimant: Pubkey, // 32
+    pub share_bps: u16, // 2
+    // `share_bps` of the ticket's amount; see `split_by_shares`
+    pub allocation: u64, // 8
+    pub claimed: u64, // 8
 }
 
 
 
+pub const MAX_SHARED_CLAIMANTS: usize = 10;
+pub const SHARED_TICKET_SEED: &[u8] = b"shared_ticket";
+pub const MERKLE_POOL_SEED: &[u8] = b"merkle_pool";
+pub const MERKLE_POSITION_SEED: &[u8] = b"merkle_position";
+// How long claimants of a fully vested merkle pool have before its authority can close it
+pub const MERKLE_POOL_CLAWBACK_GRACE: u64 = 30 * SECONDS_PER_DAY;
+
+
+
 impl ChainlinkValue {
     pub fn new(value: i128, decimals: u32) -> Self {
-        ChainlinkValue { value, decimals }
+        ChainlinkValue { value, decimals, updated_at: 0 }
+    }
+
+    // Stores `price` in Chainlink's value/decimals form; a positive exponent is folded into the value
+    pub fn set_price(&mut self, price: &NormalizedPrice, now: u64) -> Result<()> {
+        let expo = price.expo.min(0);
+        self.value = price.rescale(expo)?;
+        self.decimals = expo.unsigned_abs();
+        self.updated_at = now;
+        Ok(())
+    }
+}
+
+impl PriceHistory {
+    pub fn record(&mut self, price: NormalizedPrice, now: u64) -> Result<()> {
+        if self.len > 0 {
+            let last = self.points[(self.head as usize + PRICE_HISTORY_LEN - 1) % PRICE_HISTORY_LEN];
+            if now.saturating_sub(last.timestamp) < self.min_interval {
+                return Err(ErrorCode::PriceRecordedTooSoon.into());
+            }
+        }
+
+        self.points[self.head as usize] = PricePoint { value: price.rescale(self.expo)?, timestamp: now };
+        self.head = ((self.head as usize + 1) % PRICE_HISTORY_LEN) as u16;
+        self.len = (self.len + 1).min(PRICE_HISTORY_LEN as u16);
+
+        Ok(())
+    }
+
+    // Longest TWAP window a full history is guaranteed to cover: its points are at least
+    // `min_interval` apart, so the oldest of them is that far back from the newest per gap
+    pub fn max_window(&self) -> Result<u64> {
+        Ok((PRICE_HISTORY_LEN as u64 - 1).checked_mul(self.min_interval).ok_or(ErrorCode::MathOverflow)?)
+    }
+
+    // Time-weighted average over `[now - window, now]`. Each recorded price holds until the
+    // next one, the latest holds until `now`. The history must reach back to the window start.
+    pub fn twap(&self, window: u64, now: u64) -> Result<NormalizedPrice> {
+        let start = now.checked_sub(window).ok_or(ErrorCode::InsufficientPriceHistory)?;
+        let oldest = (self.head as usize + PRICE_HISTORY_LEN - self.len as usize) % PRICE_HISTORY_LEN;
+
+        if self.len == 0 || window == 0 || self.points[oldest].timestamp > start {
+            return Err(ErrorCode::InsufficientPriceHistory.into());
+        }
+
+        let mut weighted: i128 = 0;
+        for i in 0..self.len as usize {
+            let point = self.points[(oldest + i) % PRICE_HISTORY_LEN];
+            let until = if i + 1 < self.len as usize {
+                self.points[(oldest + i + 1) % PRICE_HISTORY_LEN].timestamp
+            } else {
+                now
+            };
+
+            let from = point.timestamp.max(start);
+            if until <= from {
+                continue;
+            }
+            weighted = point.value
+                .checked_mul((until - from) as i128)
+                .and_then(|w| weighted.checked_add(w))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        Ok(NormalizedPrice::new(weighted / window as i128, self.expo))
+    }
+}
+
+impl MerklePool {
+    // An allocation's schedule: its own amount and claims on the pool's shared terms
+    pub fn schedule(&self, position: &PoolPosition) -> VestingSchedule {
+        VestingSchedule {
+            amount: position.amount,
+            claimed: position.claimed,
+            balance: position.amount.saturating_sub(position.claimed),
+            created_at: self.created_at as i64,
+            cliff_days: self.cliff,
+            vesting_days: self.vesting,
+            ..VestingSchedule::default()
+        }
+    }
+
+    // Every allocation has fully vested `MERKLE_POOL_CLAWBACK_GRACE` before this time
+    pub fn closable_at(&self) -> Result<u64> {
+        Ok(self.vesting
+            .checked_mul(SECONDS_PER_DAY)
+            .and_then(|vesting| vesting.checked_add(self.created_at))
+            .and_then(|fully_vested_at| fully_vested_at.checked_add(MERKLE_POOL_CLAWBACK_GRACE))
+            .ok_or(ErrorCode::MathOverflow)?)
+    }
+}
+
+impl SharedTicket {
+    // A claimant's schedule: their allocation and claims on the ticket's shared terms
+    pub fn schedule(&self, beneficiary: &Beneficiary) -> VestingSchedule {
+        VestingSchedule {
+            amount: beneficiary.allocation,
+            claimed: beneficiary.claimed,
+            balance: beneficiary.allocation.saturating_sub(beneficiary.claimed),
+            created_at: self.created_at as i64,
+            cliff_days: self.cliff,
+            vesting_days: self.vesting,
+            ..VestingSchedule::default()
+        }
+    }
+
+    // Vesting stops at revocation: the unvested remainder has gone back to the owner
+    pub fn vesting_clock(&self, now: i64) -> i64 {
+        if self.is_revoked {
+            now.min(self.revoked_at as i64)
+        } else {
+            now
+        }
+    }
+}
+
+impl Vestor {
+    // Number of tickets created so far, the second seed of the next ticket's signer PDA
+    pub fn tickets_issued(&self) -> u8 {
+        self.tickets_issued
+    }
+}
+
+impl Ticket {
+    // The ticket's schedule as plain data for the pure `schedule` math
+    pub fn schedule(&self) -> VestingSchedule {
+        VestingSchedule {
+            amount: self.amount,
+            claimed: self.claimed,
+            balance: self.balance,
+            created_at: self.created_at as i64,
+            cliff_days: self.cliff,
+            vesting_days: self.vesting,
+            is_paused: self.is_paused,
+            paused_at: self.paused_at as i64,
+            total_paused: self.total_paused as i64,
+            is_revoked: self.is_revoked,
+            bonus_unlocked: self.accelerated.saturating_add(self.milestone_unlocked),
+            usd_amount: self.usd_amount,
+            usd_paid: self.usd_paid,
+            usd_unpaid: self.usd_unpaid,
+        }
+    }
+
+    // Writes the ledger fields of a schedule transition back to the ticket
+    pub fn set_ledger(&mut self, schedule: &VestingSchedule) {
+        self.amount = schedule.amount;
+        self.claimed = schedule.claimed;
+        self.balance = schedule.balance;
+        self.is_revoked = schedule.is_revoked;
+        self.usd_paid = schedule.usd_paid;
+        self.usd_unpaid = schedule.usd_unpaid;
+        self.is_paused = schedule.is_paused;
+        self.paused_at = schedule.paused_at as u64;
+        self.total_paused = schedule.total_paused as u64;
+    }
+}
+
+impl From<ScheduleError> for anchor_lang::error::Error {
+    fn from(e: ScheduleError) -> Self {
+        match e {
+            ScheduleError::Overflow => ErrorCode::MathOverflow.into(),
+            ScheduleError::ClockBeforeStart => ErrorCode::ScheduleNotStarted.into(),
+            ScheduleError::Revoked => ErrorCode::TicketRevoked.into(),
+            ScheduleError::ZeroAmount => ErrorCode::AmountMustBeGreaterThanZero.into(),
+            ScheduleError::VestingShorterThanCliff => ErrorCode::VestingPeriodShouldBeEqualOrLongerThanCliff.into(),
+            ScheduleError::ZeroVesting => ErrorCode::VestingPeriodMustBeNonZero.into(),
+            ScheduleError::InvalidShares => ErrorCode::InvalidShares.into(),
+            ScheduleError::Paused => ErrorCode::TicketPaused.into(),
+            ScheduleError::NotPaused => ErrorCode::TicketNotPaused.into(),
+        }
     }
 }
 
@@ -614,20 +2684,100 @@ impl<'info> Initialize<'info> {
     }
 }
 
-impl<'info> From<&mut CreateTicket<'info>> for CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-    fn from(accounts: &mut CreateTicket<'info>) -> Self {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: accounts
-                .ticket_creator_deposit_token_vault
-                .to_account_info()
-                .clone(),
-            current_authority: accounts.owner.to_account_info().clone(),
+impl<'info> CreateTicket<'info> {
+    fn into_transfer_to_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.ticket_creator_deposit_token_vault.to_account_info().clone(),
+            to : self.vault.to_account_info().clone(),
+            authority : self.owner.to_account_info().clone(),
         };
-        let cpi_program = accounts.token_program.to_account_info();
+        let cpi_program = self.token_program.to_account_info();
         CpiContext::new(cpi_program, cpi_accounts)
-   }
+    }
+}
+
+
+impl<'info> CreateMerklePool<'info> {
+    fn into_transfer_to_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.authority_deposit_token_vault.to_account_info().clone(),
+            to : self.vault.to_account_info().clone(),
+            authority : self.authority.to_account_info().clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'info> ClaimFromPool<'info> {
+    fn into_transfer_to_claimant_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info().clone(),
+            to : self.claimant_token_account.to_account_info().clone(),
+            authority : self.pool_signer.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'info> CloseMerklePool<'info> {
+    fn into_transfer_to_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info().clone(),
+            to : self.authority_token_account.to_account_info().clone(),
+            authority : self.pool_signer.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+
+    fn into_close_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info().clone(),
+            destination: self.authority.to_account_info().clone(),
+            authority: self.pool_signer.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'info> CreateSharedTicket<'info> {
+    fn into_transfer_to_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.ticket_creator_deposit_token_vault.to_account_info().clone(),
+            to : self.vault.to_account_info().clone(),
+            authority : self.owner.to_account_info().clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'info> ClaimShared<'info> {
+    fn into_transfer_to_claimant_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info().clone(),
+            to : self.claimant_token_account.to_account_info().clone(),
+            authority : self.signer.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
 }
 
+impl<'info> RevokeShared<'info> {
+    fn into_transfer_to_ticket_creator_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info().clone(),
+            to : self.ticket_creator_deposit_token_vault.to_account_info().clone(),
+            authority : self.signer.clone(),
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
 
 impl<'info> Claim<'info> {
     fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
@@ -676,6 +2826,280 @@ impl<'info> Revoke<'info> {
     }
 }
 
+#[event]
+pub struct VestorInitialized {
+    pub vestor: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TicketCreated {
+    pub ticket: Pubkey,
+    pub vestor: Pubkey,
+    pub owner: Pubkey,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub irrevocable: bool,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct Claimed {
+    pub ticket: Pubkey,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub usd_paid: u64,
+    pub early_release: bool,
+    pub claimed: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct Revoked {
+    pub ticket: Pubkey,
+    pub amount: u64,
+    pub claimed: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct UsdGrantSettled {
+    pub ticket: Pubkey,
+    pub usd_paid: u64,
+    pub returned: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct TicketPaused {
+    pub ticket: Pubkey,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct TicketResumed {
+    pub ticket: Pubkey,
+    pub paused_for: u64,
+    pub total_paused: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct ScheduleAmended {
+    pub ticket: Pubkey,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub vested: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct AccelerationSet {
+    pub ticket: Pubkey,
+    pub acceleration_bps: u16,
+    pub double_trigger: bool,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct TicketTerminated {
+    pub ticket: Pubkey,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct TicketAccelerated {
+    pub ticket: Pubkey,
+    pub bps: u16,
+    pub amount: u64,
+    pub accelerated: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct UsdTermsSet {
+    pub ticket: Pubkey,
+    pub usd_amount: u64,
+    pub price_feed: Pubkey,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct EarlyReleaseRequested {
+    pub ticket: Pubkey,
+    pub lottery_round: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct PriceTrancheAdded {
+    pub ticket: Pubkey,
+    pub price_tranche: Pubkey,
+    pub feed: Pubkey,
+    pub strike: i128,
+    pub strike_decimals: u32,
+    pub release_bps: u16,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct PriceTrancheReleased {
+    pub ticket: Pubkey,
+    pub price_tranche: Pubkey,
+    pub amount: u64,
+    pub milestone_unlocked: u64,
+    pub timestamp: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct ChangeOfControlSet {
+    pub vestor: Pubkey,
+    pub change_of_control: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OracleConfigUpdated {
+    pub vestor: Pubkey,
+    pub max_oracle_age: u64,
+    pub pyth_max_conf_bps: u64,
+    pub pyth_max_slot_age: u64,
+    pub max_oracle_deviation_bps: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OracleRegistryUpdated {
+    pub vestor: Pubkey,
+    pub oracles: OracleRegistry,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintPriceFeedSet {
+    pub vestor: Pubkey,
+    pub token_mint: Pubkey,
+    pub feed: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PricePolicyUpdated {
+    pub vestor: Pubkey,
+    pub price_policy: PricePolicy,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RandomnessFeedSet {
+    pub vestor: Pubkey,
+    pub randomness_feed: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RandomnessPublished {
+    pub randomness_feed: Pubkey,
+    pub round: u64,
+    pub value: [u8; 32],
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PriceHistoryInitialized {
+    pub vestor: Pubkey,
+    pub price_history: Pubkey,
+    pub feed: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PriceRecorded {
+    pub price_history: Pubkey,
+    pub value: i128,
+    pub expo: i32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MerklePoolCreated {
+    pub pool: Pubkey,
+    pub vestor: Pubkey,
+    pub root: [u8; 32],
+    pub total_amount: u64,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MerklePoolClosed {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub returned: u64,
+    pub claimed: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SharedTicketCreated {
+    pub shared_ticket: Pubkey,
+    pub vestor: Pubkey,
+    pub shares: Vec<Share>,
+    pub amount: u64,
+    pub cliff: u64,
+    pub vesting: u64,
+    pub irrevocable: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SharedTicketClaimed {
+    pub shared_ticket: Pubkey,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub claimed: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SharedTicketRevoked {
+    pub shared_ticket: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PoolClaimed {
+    pub pool: Pubkey,
+    pub index: u64,
+    pub claimant: Pubkey,
+    pub amount: u64,
+    pub claimed: u64,
+    pub timestamp: u64,
+}
+
+
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Amount must be greater than zero.")]
@@ -692,6 +3116,84 @@ pub enum ErrorCode {
     NotEnoughTokens,
     #[msg("The Program Initializer Address is incorrect")]
     InvalidProgramInitializer,
+    #[msg("Ticket is already paused")]
+    TicketPaused,
+    #[msg("Ticket is not paused")]
+    TicketNotPaused,
+    #[msg("The amended schedule would reduce the amount already vested")]
+    AmendmentReducesVestedAmount,
+    #[msg("Basis points must be between 1 and the ticket's acceleration")]
+    InvalidBasisPoints,
+    #[msg("Acceleration has not been triggered")]
+    AccelerationNotTriggered,
+    #[msg("Ticket has already been terminated")]
+    TicketTerminated,
+    #[msg("Early release is only available before the first claim")]
+    EarlyReleaseUnavailable,
+    #[msg("An early release draw has already been requested for this ticket")]
+    EarlyReleaseAlreadyRequested,
+    #[msg("Signer is not the randomness authority")]
+    InvalidRandomnessAuthority,
+    #[msg("Strike price must be greater than zero")]
+    InvalidStrikePrice,
+    #[msg("Price tranche has already been released")]
+    TrancheAlreadyReleased,
+    #[msg("Oracle feed does not match the configured feed")]
+    InvalidOracleFeed,
+    #[msg("Oracle account is not owned by the registered oracle program")]
+    InvalidOracleProgram,
+    #[msg("Oracle price is older than the maximum allowed age")]
+    StaleOracle,
+    #[msg("Oracle price must be greater than zero")]
+    InvalidOraclePrice,
+    #[msg("Oracle round is incomplete")]
+    IncompleteOracleRound,
+    #[msg("Invalid oracle configuration")]
+    InvalidOracleConfig,
+    #[msg("Pyth account could not be loaded as a price account")]
+    InvalidPythAccount,
+    #[msg("Pyth price is not currently trading")]
+    PythPriceNotTrading,
+    #[msg("Pyth confidence interval is too wide")]
+    PythConfidenceTooWide,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Clock sysvar is unavailable")]
+    ClockUnavailable,
+    #[msg("The clock is earlier than the start of the vesting schedule")]
+    ScheduleNotStarted,
+    // No longer returned now that mock feeds only exist in `mock-oracle` builds; kept so the
+    // codes after it do not change
+    #[msg("Mock price feeds are only available in mock-oracle builds")]
+    MockOracleDisabled,
+    #[msg("Not enough valid oracle sources to price the asset")]
+    InsufficientOracleSources,
+    #[msg("Tranche must be checked with the instruction matching its TWAP setting")]
+    TrancheRequiresTwap,
+    #[msg("Ticket has already been claimed from")]
+    TicketAlreadyClaimed,
+    #[msg("Price history does not cover the requested window")]
+    InsufficientPriceHistory,
+    #[msg("A price was recorded too recently")]
+    PriceRecordedTooSoon,
+    #[msg("Merkle proof does not match the pool's root")]
+    InvalidMerkleProof,
+    #[msg("Shares must all be non-zero and total 10000 bps")]
+    InvalidShares,
+    #[msg("A shared ticket can have at most 10 claimants")]
+    TooManySharedClaimants,
+    #[msg("A claimant can only hold one share of a shared ticket")]
+    DuplicateSharedClaimant,
+    #[msg("Signer holds no share of this ticket")]
+    NotASharedTicketClaimant,
+    #[msg("The vestor has no price feed registered for the ticket's mint")]
+    NoMintPriceFeed,
+    #[msg("Pool allocations can still be claimed")]
+    MerklePoolStillClaimable,
+    #[msg("Vesting period must be at least one day")]
+    VestingPeriodMustBeNonZero,
+    #[msg("USD-denominated tickets cannot unlock tokens outside their schedule")]
+    BonusUnlockOnUsdTicket,
 }
 
 
@@ -702,3 +3204,138 @@ fn not_revoked(ticket: &Ticket) -> Result<()> {
     Ok(())
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_error<T: std::fmt::Debug>(result: Result<T>, expected: ErrorCode) {
+        match result.unwrap_err() {
+            anchor_lang::error::Error::AnchorError(e) => assert_eq!(e.error_code_number, u32::from(expected)),
+            e => panic!("expected {:?}, got {:?}", expected, e),
+        }
+    }
+
+    fn schedule() -> VestingSchedule {
+        VestingSchedule {
+            amount: 1000,
+            balance: 1000,
+            created_at: 1_000_000,
+            cliff_days: 1,
+            vesting_days: 10,
+            ..VestingSchedule::default()
+        }
+    }
+
+    fn history() -> PriceHistory {
+        PriceHistory {
+            vestor: Pubkey::default(),
+            feed: Pubkey::default(),
+            expo: -8,
+            min_interval: 60,
+            head: 0,
+            len: 0,
+            points: vec![PricePoint::default(); PRICE_HISTORY_LEN],
+        }
+    }
+
+    #[test]
+    fn clock_outside_the_runtime_is_clock_unavailable() {
+        assert_error(current_clock(), ErrorCode::ClockUnavailable);
+    }
+
+    #[test]
+    fn schedule_overflow_is_math_overflow() {
+        let schedule = VestingSchedule { vesting_days: u64::MAX, ..schedule() };
+        assert_error(schedule.available(2_000_000).map_err(Into::into), ErrorCode::MathOverflow);
+    }
+
+    #[test]
+    fn clock_before_creation_is_schedule_not_started() {
+        assert_error(schedule().vested(999_999).map_err(Into::into), ErrorCode::ScheduleNotStarted);
+    }
+
+    #[test]
+    fn rescaling_past_i128_is_math_overflow() {
+        assert_error(NormalizedPrice::new(1, 0).rescale(-60), ErrorCode::MathOverflow);
+    }
+
+    #[test]
+    fn deviation_from_a_zero_reference_is_invalid_oracle_price() {
+        let price = NormalizedPrice::new(100, -2);
+        assert_error(price.deviation_bps(&NormalizedPrice::new(0, -2)), ErrorCode::InvalidOraclePrice);
+    }
+
+    #[test]
+    fn converting_usd_at_a_zero_price_is_invalid_oracle_price() {
+        assert_error(NormalizedPrice::new(0, -8).usd_to_tokens(1_000_000, 9), ErrorCode::InvalidOraclePrice);
+    }
+
+    #[test]
+    fn too_few_valid_sources_is_insufficient_oracle_sources() {
+        let policy = PricePolicy { mode: AggregationMode::Median, min_valid_sources: 2 };
+        let readings = [Some(NormalizedPrice::new(100, -2)), None];
+
+        assert_error(aggregate(&readings, &policy), ErrorCode::InsufficientOracleSources);
+        assert_error(aggregate(&[None, None], &PricePolicy::default()), ErrorCode::InsufficientOracleSources);
+    }
+
+    #[test]
+    fn price_policy_must_be_satisfiable_by_the_claim_sources() {
+        let policy = |min_valid_sources| PricePolicy { mode: AggregationMode::Median, min_valid_sources };
+
+        assert_error(policy(0).validate(CLAIM_PRICE_SOURCES), ErrorCode::InvalidOracleConfig);
+        assert!(policy(1).validate(CLAIM_PRICE_SOURCES).is_ok());
+        assert!(policy(2).validate(CLAIM_PRICE_SOURCES).is_ok());
+        assert_error(policy(3).validate(CLAIM_PRICE_SOURCES), ErrorCode::InvalidOracleConfig);
+    }
+
+    #[test]
+    fn garbage_pyth_data_is_invalid_pyth_account() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![7u8; 64];
+        let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+
+        assert_error(validated_pyth_price(&account, 100, 25, 0), ErrorCode::InvalidPythAccount);
+    }
+
+    #[test]
+    fn feeds_must_belong_to_the_registered_chainlink_program() {
+        let vestor = Vestor::default();
+        assert!(is_allowed_feed_owner(&vestor.oracles.chainlink_program, &ID, &vestor));
+        assert!(!is_allowed_feed_owner(&Pubkey::new_unique(), &ID, &vestor));
+        // This program's own accounts are `MockPriceFeed`s, only readable in mock-oracle builds
+        assert_eq!(is_allowed_feed_owner(&ID, &ID, &vestor), cfg!(feature = "mock-oracle"));
+    }
+
+    #[test]
+    fn twap_without_history_is_insufficient_price_history() {
+        assert_error(history().twap(60, 1_000), ErrorCode::InsufficientPriceHistory);
+    }
+
+    #[test]
+    fn full_history_covers_exactly_the_max_window() {
+        let mut history = history();
+        let mut now = 1_000;
+        for _ in 0..PRICE_HISTORY_LEN * 2 {
+            history.record(NormalizedPrice::new(100, -8), now).unwrap();
+            now += history.min_interval;
+        }
+        now -= history.min_interval;
+
+        assert_eq!(history.max_window().unwrap(), 63 * 60);
+        assert_eq!(history.twap(history.max_window().unwrap(), now).unwrap(), NormalizedPrice::new(100, -8));
+        assert_error(history.twap(history.max_window().unwrap() + 1, now), ErrorCode::InsufficientPriceHistory);
+    }
+
+    #[test]
+    fn recording_within_min_interval_is_price_recorded_too_soon() {
+        let mut history = history();
+        history.record(NormalizedPrice::new(100, -8), 1_000).unwrap();
+
+        assert_error(history.record(NormalizedPrice::new(101, -8), 1_030), ErrorCode::PriceRecordedTooSoon);
+    }
+}
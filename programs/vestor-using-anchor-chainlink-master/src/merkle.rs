@@ -0,0 +1,57 @@
+// Merkle proofs for vesting pools. Leaves and inner nodes are hashed with different
+// prefixes so an inner node can never be passed off as a leaf, and each pair is sorted
+// before hashing so a proof is just the list of siblings from the leaf up.
+// `root` and `proof` build the same tree off-chain that `verify` checks on-chain.
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::hash::hashv;
+
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+
+// One recipient's allocation in a pool
+pub fn leaf(index: u64, claimant: &Pubkey, amount: u64) -> [u8; 32] {
+    hashv(&[LEAF_PREFIX, &index.to_le_bytes(), claimant.as_ref(), &amount.to_le_bytes()]).to_bytes()
+}
+
+fn node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, first, second]).to_bytes()
+}
+
+pub fn verify(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |hash, sibling| node(&hash, sibling));
+    &computed == root
+}
+
+
+// Each level of the tree from the leaves up. A node without a sibling is carried up as is.
+fn levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.to_vec()];
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let next = level
+            .chunks(2)
+            .map(|pair| if pair.len() == 2 { node(&pair[0], &pair[1]) } else { pair[0] })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+// All zeroes for an empty tree
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    levels(leaves).last().and_then(|level| level.first().copied()).unwrap_or([0; 32])
+}
+
+pub fn proof(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    let mut proof = Vec::new();
+    let mut position = index;
+    for level in levels(leaves).iter().take_while(|level| level.len() > 1) {
+        if let Some(sibling) = level.get(position ^ 1) {
+            proof.push(*sibling);
+        }
+        position /= 2;
+    }
+    proof
+}
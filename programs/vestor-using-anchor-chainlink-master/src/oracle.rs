@@ -0,0 +1,405 @@
+use anchor_lang::prelude::*;
+use std::convert::TryFrom;
+use chainlink_solana as chainlink;
+use pyth_client::{load_price, Price, PriceStatus};
+
+use crate::{ChainlinkValue, ErrorCode};
+#[cfg(feature = "mock-oracle")]
+use crate::MockPriceFeed;
+
+
+// Used for vestors until the authority configures their own maximum age.
+pub const DEFAULT_MAX_ORACLE_AGE: u64 = 300;
+pub const DEFAULT_PYTH_MAX_CONF_BPS: u64 = 100;
+pub const DEFAULT_PYTH_MAX_SLOT_AGE: u64 = 25;
+pub const DEFAULT_MAX_ORACLE_DEVIATION_BPS: u64 = 50;
+
+// USD amounts on tickets are stored in micro-dollars
+pub const USD_DECIMALS: u32 = 6;
+
+
+// A price as `value * 10^expo`. Chainlink and Pyth report with different scales,
+// so prices from either are converted to this before being compared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalizedPrice {
+    pub value: i128,
+    pub expo: i32,
+}
+
+impl NormalizedPrice {
+    pub fn new(value: i128, expo: i32) -> Self {
+        NormalizedPrice { value, expo }
+    }
+
+    // The value expressed with exponent `expo`, truncating when precision is dropped
+    pub fn rescale(&self, expo: i32) -> Result<i128> {
+        let shift = 10i128
+            .checked_pow((self.expo - expo).unsigned_abs())
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        if expo <= self.expo {
+            Ok(self.value.checked_mul(shift).ok_or(ErrorCode::MathOverflow)?)
+        } else {
+            Ok(self.value / shift)
+        }
+    }
+
+    // Both values expressed with the finer of the two exponents
+    fn aligned(&self, other: &NormalizedPrice) -> Result<(i128, i128)> {
+        let expo = self.expo.min(other.expo);
+        Ok((self.rescale(expo)?, other.rescale(expo)?))
+    }
+
+    pub fn is_above(&self, other: &NormalizedPrice) -> Result<bool> {
+        let (a, b) = self.aligned(other)?;
+        Ok(a > b)
+    }
+
+    pub fn is_at_or_above(&self, other: &NormalizedPrice) -> Result<bool> {
+        let (a, b) = self.aligned(other)?;
+        Ok(a >= b)
+    }
+
+    // |self - reference| in basis points of `reference`
+    pub fn deviation_bps(&self, reference: &NormalizedPrice) -> Result<u64> {
+        let (a, b) = self.aligned(reference)?;
+        if b <= 0 {
+            return err!(ErrorCode::InvalidOraclePrice);
+        }
+
+        let bps = a
+            .checked_sub(b)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_abs()
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(10000)
+            .ok_or(ErrorCode::MathOverflow)?
+            / b;
+        Ok(u64::try_from(bps).map_err(|_| ErrorCode::MathOverflow)?)
+    }
+}
+
+impl NormalizedPrice {
+    // Splits `10^(expo + USD_DECIMALS)` into a (multiplier, divisor) pair of powers of ten
+    fn usd_scale(&self) -> Result<(u128, u128)> {
+        let e = self.expo + USD_DECIMALS as i32;
+        let pow = 10u128.checked_pow(e.unsigned_abs()).ok_or(ErrorCode::MathOverflow)?;
+        Ok(if e >= 0 { (pow, 1) } else { (1, pow) })
+    }
+
+    // How many base units of a token priced at `self` USD per whole token `usd` micro-dollars buy
+    pub fn usd_to_tokens(&self, usd: u64, mint_decimals: u8) -> Result<u64> {
+        if self.value <= 0 {
+            return err!(ErrorCode::InvalidOraclePrice);
+        }
+        let (mul, div) = self.usd_scale()?;
+        let unit = 10u128.checked_pow(mint_decimals as u32).ok_or(ErrorCode::MathOverflow)?;
+
+        let tokens = (usd as u128)
+            .checked_mul(unit)
+            .and_then(|v| v.checked_mul(div))
+            .ok_or(ErrorCode::MathOverflow)?
+            / (self.value as u128).checked_mul(mul).ok_or(ErrorCode::MathOverflow)?;
+        Ok(u64::try_from(tokens).map_err(|_| ErrorCode::MathOverflow)?)
+    }
+
+    // Micro-dollar value of `tokens` base units, the inverse of `usd_to_tokens`
+    pub fn tokens_to_usd(&self, tokens: u64, mint_decimals: u8) -> Result<u64> {
+        if self.value <= 0 {
+            return err!(ErrorCode::InvalidOraclePrice);
+        }
+        let (mul, div) = self.usd_scale()?;
+        let unit = 10u128.checked_pow(mint_decimals as u32).ok_or(ErrorCode::MathOverflow)?;
+
+        let usd = (tokens as u128)
+            .checked_mul(self.value as u128)
+            .and_then(|v| v.checked_mul(mul))
+            .ok_or(ErrorCode::MathOverflow)?
+            / unit.checked_mul(div).ok_or(ErrorCode::MathOverflow)?;
+        Ok(u64::try_from(usd).map_err(|_| ErrorCode::MathOverflow)?)
+    }
+}
+
+impl From<&ChainlinkValue> for NormalizedPrice {
+    fn from(price: &ChainlinkValue) -> Self {
+        NormalizedPrice::new(price.value, -(price.decimals as i32))
+    }
+}
+
+impl From<&Price> for NormalizedPrice {
+    fn from(price: &Price) -> Self {
+        NormalizedPrice::new(price.agg.price as i128, price.expo)
+    }
+}
+
+
+// Every Chainlink read in the program goes through here. The round is only returned
+// if it is complete, has a positive answer and was updated within `max_age` seconds of `now`.
+pub fn validated_round<'info>(
+    chainlink_program: AccountInfo<'info>,
+    feed: AccountInfo<'info>,
+    max_age: u64,
+    now: u64,
+) -> Result<chainlink::Round> {
+    let round = chainlink::latest_round_data(chainlink_program, feed)?;
+
+    if round.round_id == 0 || round.timestamp == 0 {
+        return err!(ErrorCode::IncompleteOracleRound);
+    }
+    if round.answer <= 0 {
+        return err!(ErrorCode::InvalidOraclePrice);
+    }
+
+    if now.saturating_sub(round.timestamp as u64) > max_age {
+        return err!(ErrorCode::StaleOracle);
+    }
+
+    Ok(round)
+}
+
+
+// Pyth counterpart of `validated_round`. Only accepts a positive aggregate price with
+// Trading status, a confidence interval no wider than `max_conf_bps` of the price and
+// a publish slot at most `max_slot_age` slots behind `current_slot`.
+pub fn validated_pyth_price(
+    pyth_account: &AccountInfo,
+    max_conf_bps: u64,
+    max_slot_age: u64,
+    current_slot: u64,
+) -> Result<Price> {
+    let data = pyth_account.try_borrow_data()?;
+    let price_account: Price = *load_price(&data).map_err(|_| ErrorCode::InvalidPythAccount)?;
+
+    if !matches!(price_account.agg.status, PriceStatus::Trading) {
+        return err!(ErrorCode::PythPriceNotTrading);
+    }
+    if price_account.agg.price <= 0 {
+        return err!(ErrorCode::InvalidOraclePrice);
+    }
+
+    let max_conf = (price_account.agg.price as u128)
+        .checked_mul(max_conf_bps as u128)
+        .ok_or(ErrorCode::InvalidOraclePrice)?
+        / 10000;
+    if price_account.agg.conf as u128 > max_conf {
+        return err!(ErrorCode::PythConfidenceTooWide);
+    }
+
+    if current_slot.saturating_sub(price_account.agg.pub_slot) > max_slot_age {
+        return err!(ErrorCode::StaleOracle);
+    }
+
+    Ok(price_account)
+}
+
+
+// Anything the program can read a validated price from. Vesting logic that depends on a
+// price takes a `PriceSource` rather than a concrete oracle, so it can run against
+// `MockPriceFeed` on a local validator.
+pub trait PriceSource {
+    fn price(&self, now: u64, slot: u64) -> Result<NormalizedPrice>;
+}
+
+
+pub struct ChainlinkSource<'a, 'info> {
+    pub program: &'a AccountInfo<'info>,
+    pub feed: &'a AccountInfo<'info>,
+    pub max_age: u64,
+}
+
+impl<'a, 'info> ChainlinkSource<'a, 'info> {
+    pub fn new(program: &'a AccountInfo<'info>, feed: &'a AccountInfo<'info>, max_age: u64) -> Self {
+        ChainlinkSource { program, feed, max_age }
+    }
+
+    pub fn value(&self, now: u64) -> Result<ChainlinkValue> {
+        let round = validated_round(self.program.clone(), self.feed.clone(), self.max_age, now)?;
+        let decimals = chainlink::decimals(self.program.clone(), self.feed.clone())?;
+        Ok(ChainlinkValue::new(round.answer, u32::from(decimals)))
+    }
+}
+
+impl<'a, 'info> PriceSource for ChainlinkSource<'a, 'info> {
+    fn price(&self, now: u64, _slot: u64) -> Result<NormalizedPrice> {
+        Ok(NormalizedPrice::from(&self.value(now)?))
+    }
+}
+
+
+pub struct PythSource<'a, 'info> {
+    pub account: &'a AccountInfo<'info>,
+    pub max_conf_bps: u64,
+    pub max_slot_age: u64,
+}
+
+impl<'a, 'info> PythSource<'a, 'info> {
+    pub fn new(account: &'a AccountInfo<'info>, max_conf_bps: u64, max_slot_age: u64) -> Self {
+        PythSource { account, max_conf_bps, max_slot_age }
+    }
+}
+
+impl<'a, 'info> PriceSource for PythSource<'a, 'info> {
+    fn price(&self, _now: u64, slot: u64) -> Result<NormalizedPrice> {
+        let price_account = validated_pyth_price(self.account, self.max_conf_bps, self.max_slot_age, slot)?;
+        Ok(NormalizedPrice::from(&price_account))
+    }
+}
+
+
+// Only compiled into `mock-oracle` builds, so a mock feed can never stand in for a real
+// oracle on a production deployment.
+#[cfg(feature = "mock-oracle")]
+impl PriceSource for MockPriceFeed {
+    fn price(&self, _now: u64, _slot: u64) -> Result<NormalizedPrice> {
+        if self.value <= 0 {
+            return err!(ErrorCode::InvalidOraclePrice);
+        }
+
+        Ok(NormalizedPrice::new(self.value, self.expo))
+    }
+}
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AggregationMode {
+    // Median of every valid source
+    Median,
+    // First valid source in priority order, falling back to the next when it is stale or invalid
+    Fallback,
+}
+
+
+// How a vestor combines its price sources
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PricePolicy {
+    pub mode: AggregationMode, // 1
+    pub min_valid_sources: u8, // 1
+}
+
+impl Default for PricePolicy {
+    fn default() -> Self {
+        PricePolicy { mode: AggregationMode::Median, min_valid_sources: 1 }
+    }
+}
+
+impl PricePolicy {
+    // A policy that asks for more valid readings than there are sources can never be met
+    pub fn validate(&self, sources: usize) -> Result<()> {
+        if self.min_valid_sources == 0 || self.min_valid_sources as usize > sources {
+            return err!(ErrorCode::InvalidOracleConfig);
+        }
+        Ok(())
+    }
+}
+
+
+// SOL readings `claim` passes to `aggregate`: Chainlink, then Pyth
+pub const CLAIM_PRICE_SOURCES: usize = 2;
+
+
+// Combines readings given in priority order (primary first). Invalid readings (`None`) are
+// skipped, and pricing only fails when fewer than `min_valid_sources` of them are valid.
+pub fn aggregate(readings: &[Option<NormalizedPrice>], policy: &PricePolicy) -> Result<NormalizedPrice> {
+    let valid: Vec<NormalizedPrice> = readings.iter().filter_map(|r| *r).collect();
+
+    if valid.is_empty() || valid.len() < policy.min_valid_sources as usize {
+        return err!(ErrorCode::InsufficientOracleSources);
+    }
+
+    match policy.mode {
+        AggregationMode::Fallback => Ok(valid[0]),
+        AggregationMode::Median => median(&valid),
+    }
+}
+
+
+fn median(prices: &[NormalizedPrice]) -> Result<NormalizedPrice> {
+    let expo = prices.iter().map(|p| p.expo).min().ok_or(ErrorCode::InsufficientOracleSources)?;
+    let mut values = prices.iter().map(|p| p.rescale(expo)).collect::<Result<Vec<i128>>>()?;
+    values.sort_unstable();
+
+    let mid = values.len() / 2;
+    let value = if values.len() % 2 == 0 {
+        // Halve before adding so two large prices cannot overflow
+        values[mid - 1] / 2 + values[mid] / 2 + (values[mid - 1] % 2 + values[mid] % 2) / 2
+    } else {
+        values[mid]
+    };
+
+    Ok(NormalizedPrice::new(value, expo))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_error<T: std::fmt::Debug>(result: Result<T>, expected: ErrorCode) {
+        match result.unwrap_err() {
+            anchor_lang::error::Error::AnchorError(e) => assert_eq!(e.error_code_number, u32::from(expected)),
+            e => panic!("expected {:?}, got {:?}", expected, e),
+        }
+    }
+
+    #[test]
+    fn rescales_to_finer_and_coarser_exponents() {
+        let price = NormalizedPrice::new(12_345, -2);
+        assert_eq!(price.rescale(-2).unwrap(), 12_345);
+        assert_eq!(price.rescale(-5).unwrap(), 12_345_000);
+        // Coarser exponents truncate
+        assert_eq!(price.rescale(0).unwrap(), 123);
+        assert_eq!(price.rescale(3).unwrap(), 0);
+
+        let price = NormalizedPrice::new(5, 3);
+        assert_eq!(price.rescale(0).unwrap(), 5_000);
+        assert_eq!(price.rescale(-2).unwrap(), 500_000);
+    }
+
+    #[test]
+    fn compares_prices_with_different_exponents() {
+        // $100 from an 8-decimal Chainlink feed and a Pyth price with expo -6
+        let chainlink = NormalizedPrice::new(10_000_000_000, -8);
+        let pyth = NormalizedPrice::new(100_000_000, -6);
+
+        assert!(chainlink.is_at_or_above(&pyth).unwrap());
+        assert!(pyth.is_at_or_above(&chainlink).unwrap());
+        assert!(!chainlink.is_above(&pyth).unwrap());
+        assert_eq!(chainlink.deviation_bps(&pyth).unwrap(), 0);
+
+        let higher = NormalizedPrice::new(10_001, -2);
+        assert!(higher.is_above(&pyth).unwrap());
+        assert!(!pyth.is_at_or_above(&higher).unwrap());
+    }
+
+    #[test]
+    fn deviation_is_in_bps_of_the_reference_either_side() {
+        let reference = NormalizedPrice::new(100_000_000, -6);
+
+        assert_eq!(NormalizedPrice::new(10_100, -2).deviation_bps(&reference).unwrap(), 100);
+        assert_eq!(NormalizedPrice::new(99, 0).deviation_bps(&reference).unwrap(), 100);
+        assert_eq!(NormalizedPrice::new(9_999_950_000, -8).deviation_bps(&reference).unwrap(), 0);
+        assert_eq!(NormalizedPrice::new(1_505, -1).deviation_bps(&reference).unwrap(), 5_050);
+    }
+
+    #[test]
+    fn deviation_needs_a_positive_reference() {
+        let price = NormalizedPrice::new(100, -2);
+        assert_error(price.deviation_bps(&NormalizedPrice::new(0, -2)), ErrorCode::InvalidOraclePrice);
+        assert_error(price.deviation_bps(&NormalizedPrice::new(-100, -2)), ErrorCode::InvalidOraclePrice);
+    }
+
+    #[test]
+    fn overflow_is_math_overflow() {
+        // 10^60 does not fit an i128
+        assert_error(NormalizedPrice::new(1, 0).rescale(-60), ErrorCode::MathOverflow);
+        assert_error(NormalizedPrice::new(i128::MAX / 5, 0).rescale(-1), ErrorCode::MathOverflow);
+        assert_error(
+            NormalizedPrice::new(i128::MAX / 10, -1).is_above(&NormalizedPrice::new(1, -30)),
+            ErrorCode::MathOverflow,
+        );
+        assert_error(
+            NormalizedPrice::new(i128::MAX / 2, 0).deviation_bps(&NormalizedPrice::new(1, 0)),
+            ErrorCode::MathOverflow,
+        );
+    }
+}
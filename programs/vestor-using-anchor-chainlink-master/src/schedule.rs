@@ -0,0 +1,553 @@
+// Pure vesting math. Nothing in here reads the clock or an account: callers pass the
+// schedule and `now` explicitly, so the same code runs on-chain and in off-chain clients.
+use anchor_lang::prelude::{AnchorDeserialize, AnchorSerialize};
+
+
+pub const SECONDS_PER_DAY: u64 = 86400;
+
+// The shares of a shared ticket always add up to exactly this
+pub const TOTAL_SHARE_BPS: u16 = 10_000;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    // An intermediate value did not fit its integer type
+    Overflow,
+    // `now` is earlier than the start of the schedule
+    ClockBeforeStart,
+    // The ticket has been revoked and can no longer change
+    Revoked,
+    // A new ticket must grant a non-zero amount
+    ZeroAmount,
+    // A new ticket's vesting period must be at least as long as its cliff
+    VestingShorterThanCliff,
+    // A zero-day vesting period would vest the whole grant at once
+    ZeroVesting,
+    // Shares must all be non-zero and add up to `TOTAL_SHARE_BPS`
+    InvalidShares,
+    // Vesting is already paused
+    Paused,
+    // Vesting is not paused, so there is nothing to resume
+    NotPaused,
+}
+
+
+// Terms every new ticket must satisfy. `create_ticket` and `amend_schedule` enforce these,
+// and off-chain planners run the same check before sending anything.
+pub fn validate_terms(amount: u64, cliff_days: u64, vesting_days: u64) -> Result<(), ScheduleError> {
+    if amount == 0 {
+        return Err(ScheduleError::ZeroAmount);
+    }
+    if vesting_days < cliff_days {
+        return Err(ScheduleError::VestingShorterThanCliff);
+    }
+    if vesting_days == 0 {
+        return Err(ScheduleError::ZeroVesting);
+    }
+
+    Ok(())
+}
+
+
+// Splits `amount` pro-rata by `shares_bps`. Each share is rounded down and the last one
+// takes the rounding dust, so the allocations always add up to exactly `amount`.
+pub fn split_by_shares(amount: u64, shares_bps: &[u16]) -> Result<Vec<u64>, ScheduleError> {
+    let total: u64 = shares_bps.iter().map(|&bps| bps as u64).sum();
+    if shares_bps.is_empty() || shares_bps.contains(&0) || total != TOTAL_SHARE_BPS as u64 {
+        return Err(ScheduleError::InvalidShares);
+    }
+
+    let mut allocations: Vec<u64> = shares_bps
+        .iter()
+        .map(|&bps| (amount as u128 * bps as u128 / TOTAL_SHARE_BPS as u128) as u64)
+        .collect();
+    let allocated: u64 = allocations.iter().sum();
+    if let Some(last) = allocations.last_mut() {
+        *last += amount - allocated;
+    }
+
+    Ok(allocations)
+}
+
+
+// Everything the vesting math needs to know about a ticket. Timestamps are unix seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VestingSchedule {
+    pub amount: u64,
+    pub claimed: u64,
+    pub balance: u64,
+    pub created_at: i64,
+    pub cliff_days: u64,
+    pub vesting_days: u64,
+    pub is_paused: bool,
+    pub paused_at: i64,
+    pub total_paused: i64,
+    pub is_revoked: bool,
+    // Tokens unlocked outside of the linear schedule (acceleration and price milestones)
+    pub bonus_unlocked: u64,
+    // Micro-dollars granted, for USD-denominated tickets
+    pub usd_amount: u64,
+    // Micro-dollars already paid out on a USD-denominated ticket
+    pub usd_paid: u64,
+    // Micro-dollars the last USD claim owed but could not pay from the vault
+    pub usd_unpaid: u64,
+}
+
+
+// What one claim on a USD-denominated ticket paid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UsdPayout {
+    // Token base units sent to the claimant
+    pub tokens: u64,
+    // Micro-dollars those tokens settled
+    pub usd: u64,
+    // Tokens left over once the grant is paid in full, which go back to the ticket owner
+    pub returned: u64,
+}
+
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VestingStatus {
+    pub vested: u64, // 8
+    pub claimable: u64, // 8
+    pub locked: u64, // 8
+    pub next_unlock_at: u64, // 8
+    pub fully_vested_at: u64, // 8
+    // Micro-dollars a claim would pay on a USD-denominated ticket. Their token amount is only
+    // known at the oracle price when the claim runs, so `claimable` is 0 on those tickets.
+    pub usd_claimable: u64, // 8
+}
+
+
+impl VestingSchedule {
+    fn cliff_seconds(&self) -> Result<u64, ScheduleError> {
+        self.cliff_days.checked_mul(SECONDS_PER_DAY).ok_or(ScheduleError::Overflow)
+    }
+
+    fn vesting_seconds(&self) -> Result<u64, ScheduleError> {
+        self.vesting_days.checked_mul(SECONDS_PER_DAY).ok_or(ScheduleError::Overflow)
+    }
+
+    // Seconds of vesting accrued since creation, excluding every pause.
+    // While paused the clock stops at `paused_at`.
+    pub fn elapsed(&self, now: i64) -> Result<u64, ScheduleError> {
+        let until = if self.is_paused { self.paused_at } else { now };
+
+        let elapsed = until
+            .checked_sub(self.created_at)
+            .and_then(|e| e.checked_sub(self.total_paused))
+            .ok_or(ScheduleError::Overflow)?;
+        if elapsed < 0 {
+            return Err(ScheduleError::ClockBeforeStart);
+        }
+
+        Ok(elapsed as u64)
+    }
+
+    pub fn has_cliffed(&self, now: i64) -> Result<bool, ScheduleError> {
+        if self.cliff_days == 0 {
+            return Ok(true);
+        }
+
+        Ok(self.elapsed(now)? > self.cliff_seconds()?)
+    }
+
+    // `total * elapsed / vesting`, reaching `total` once the vesting period is over
+    fn linear(&self, total: u64, now: i64) -> Result<u64, ScheduleError> {
+        let elapsed = self.elapsed(now)?;
+        let vesting = self.vesting_seconds()?;
+
+        if elapsed >= vesting {
+            return Ok(total);
+        }
+
+        let linear = (elapsed as u128)
+            .checked_mul(total as u128)
+            .ok_or(ScheduleError::Overflow)?
+            / vesting as u128;
+        Ok(linear as u64)
+    }
+
+    // Linear schedule plus bonus unlocks, never more than `amount`. Ignores the cliff.
+    pub fn unlocked(&self, now: i64) -> Result<u64, ScheduleError> {
+        let unlocked = self
+            .linear(self.amount, now)?
+            .checked_add(self.bonus_unlocked)
+            .ok_or(ScheduleError::Overflow)?;
+
+        Ok(unlocked.min(self.amount))
+    }
+
+    // Total amount vested to date, including whatever has already been claimed
+    pub fn vested(&self, now: i64) -> Result<u64, ScheduleError> {
+        if self.has_cliffed(now)? {
+            self.unlocked(now)
+        } else {
+            // Bonus unlocks vest immediately, even before the cliff
+            Ok(self.bonus_unlocked.min(self.amount))
+        }
+    }
+
+    // What can be claimed right now
+    pub fn available(&self, now: i64) -> Result<u64, ScheduleError> {
+        Ok(self.vested(now)?.saturating_sub(self.claimed).min(self.balance))
+    }
+
+    // Micro-dollars of a USD-denominated grant vested to date, on the same cliff and schedule
+    pub fn usd_vested(&self, now: i64) -> Result<u64, ScheduleError> {
+        if !self.has_cliffed(now)? {
+            return Ok(0);
+        }
+
+        self.linear(self.usd_amount, now)
+    }
+
+    // Micro-dollars vested but not paid yet on a USD-denominated ticket
+    pub fn usd_available(&self, now: i64) -> Result<u64, ScheduleError> {
+        Ok(self.usd_vested(now)?.saturating_sub(self.usd_paid))
+    }
+
+    // Snapshot of the schedule at `now`. Timestamps are 0 when there is nothing left
+    // to unlock or the ticket is paused.
+    pub fn status(&self, now: i64) -> Result<VestingStatus, ScheduleError> {
+        let vested = self.vested(now)?;
+
+        // The schedule effectively starts later by every second the ticket spent paused
+        let paused_for = if self.is_paused { now.saturating_sub(self.paused_at).max(0) } else { 0 };
+        let start = self
+            .created_at
+            .checked_add(self.total_paused)
+            .and_then(|s| s.checked_add(paused_for))
+            .ok_or(ScheduleError::Overflow)? as u64;
+        let vesting = self.vesting_seconds()?;
+
+        let next_unlock_at = if self.is_paused || self.is_revoked || vested >= self.amount {
+            0
+        } else if !self.has_cliffed(now)? {
+            start + self.cliff_seconds()? + 1
+        } else {
+            // First second at which the linear schedule reaches one more base unit
+            let linear = vested.saturating_sub(self.bonus_unlocked) as u128;
+            let needed = (linear + 1)
+                .checked_mul(vesting as u128)
+                .ok_or(ScheduleError::Overflow)?
+                .checked_add(self.amount as u128 - 1)
+                .ok_or(ScheduleError::Overflow)?
+                / self.amount as u128;
+            start.checked_add(needed as u64).ok_or(ScheduleError::Overflow)?
+        };
+
+        let fully_vested_at = if self.is_paused || self.is_revoked {
+            0
+        } else {
+            start.checked_add(vesting).ok_or(ScheduleError::Overflow)?
+        };
+
+        let is_usd = self.usd_amount > 0;
+        Ok(VestingStatus {
+            vested,
+            claimable: if self.is_revoked || is_usd { 0 } else { self.available(now)? },
+            locked: self.amount.saturating_sub(vested),
+            next_unlock_at,
+            fully_vested_at,
+            usd_claimable: if self.is_revoked || !is_usd { 0 } else { self.usd_available(now)? },
+        })
+    }
+
+    // Ledger transitions. The program applies these and writes `claimed`, `balance`,
+    // `amount`, `is_revoked` and the USD totals back to the ticket, so they keep
+    // `claimed + balance == amount` until revocation or a USD grant is settled.
+
+    // Pays out everything available at `now`. Returns the amount paid.
+    pub fn claim(&mut self, now: i64) -> Result<u64, ScheduleError> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked);
+        }
+
+        let amount = self.available(now)?;
+        self.claimed = self.claimed.checked_add(amount).ok_or(ScheduleError::Overflow)?;
+        self.balance = self.balance.checked_sub(amount).ok_or(ScheduleError::Overflow)?;
+
+        Ok(amount)
+    }
+
+    // Early release: pays out the whole remaining balance at once. Returns the amount paid.
+    pub fn release_early(&mut self) -> Result<u64, ScheduleError> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked);
+        }
+
+        let amount = self.balance;
+        self.claimed = self.claimed.checked_add(amount).ok_or(ScheduleError::Overflow)?;
+        self.balance = 0;
+
+        Ok(amount)
+    }
+
+    // Pays what a USD-denominated ticket owes at `now`. `to_tokens` and `to_usd` convert
+    // between micro-dollars and token base units at the current price. At most `vault_amount`
+    // tokens go out; when that falls short only the dollars they are worth count as paid,
+    // and the rest is recorded in `usd_unpaid`. The claim that pays the last dollar settles
+    // the grant, releasing whatever balance is left to be returned.
+    pub fn claim_usd<E: From<ScheduleError>>(
+        &mut self,
+        now: i64,
+        vault_amount: u64,
+        to_tokens: impl Fn(u64) -> Result<u64, E>,
+        to_usd: impl Fn(u64) -> Result<u64, E>,
+    ) -> Result<UsdPayout, E> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked.into());
+        }
+
+        let usd_due = self.usd_available(now)?;
+        let payable = self.balance.min(vault_amount);
+        let tokens = to_tokens(usd_due)?.min(payable);
+        let usd = if tokens == payable { to_usd(tokens)?.min(usd_due) } else { usd_due };
+
+        self.usd_paid = self.usd_paid.checked_add(usd).ok_or(ScheduleError::Overflow)?;
+        self.usd_unpaid = usd_due - usd;
+        self.claimed = self.claimed.checked_add(tokens).ok_or(ScheduleError::Overflow)?;
+        self.balance -= tokens;
+
+        let returned = if self.is_usd_settled() { self.balance } else { 0 };
+        self.balance -= returned;
+
+        Ok(UsdPayout { tokens, usd, returned })
+    }
+
+    // A USD-denominated grant is settled once every micro-dollar of it has been paid
+    pub fn is_usd_settled(&self) -> bool {
+        self.usd_amount > 0 && self.usd_paid == self.usd_amount
+    }
+
+    // Returns the whole remaining balance to the creator. Returns the amount returned.
+    pub fn revoke(&mut self) -> Result<u64, ScheduleError> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked);
+        }
+
+        let returned = self.balance;
+        self.balance = 0;
+        self.is_revoked = true;
+
+        Ok(returned)
+    }
+
+    // Stops the vesting clock at `now` until `resume`.
+    pub fn pause(&mut self, now: i64) -> Result<(), ScheduleError> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked);
+        }
+        if self.is_paused {
+            return Err(ScheduleError::Paused);
+        }
+
+        self.is_paused = true;
+        self.paused_at = now;
+
+        Ok(())
+    }
+
+    // Restarts the vesting clock where `pause` stopped it, pushing the end of the schedule
+    // out by the length of the pause. Returns that length in seconds.
+    pub fn resume(&mut self, now: i64) -> Result<i64, ScheduleError> {
+        if self.is_revoked {
+            return Err(ScheduleError::Revoked);
+        }
+        if !self.is_paused {
+            return Err(ScheduleError::NotPaused);
+        }
+
+        let paused_for = now.checked_sub(self.paused_at).filter(|p| *p >= 0).ok_or(ScheduleError::Overflow)?;
+        self.total_paused = self.total_paused.checked_add(paused_for).ok_or(ScheduleError::Overflow)?;
+        self.is_paused = false;
+        self.paused_at = 0;
+
+        Ok(paused_for)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREATED_AT: i64 = 1_650_000_000;
+    const DAY: i64 = SECONDS_PER_DAY as i64;
+
+    // 1000 tokens, 10 day cliff, 100 day vesting
+    fn schedule() -> VestingSchedule {
+        VestingSchedule {
+            amount: 1000,
+            balance: 1000,
+            created_at: CREATED_AT,
+            cliff_days: 10,
+            vesting_days: 100,
+            ..VestingSchedule::default()
+        }
+    }
+
+    #[test]
+    fn cliff_passes_strictly_after_cliff_days() {
+        let s = schedule();
+        assert!(!s.has_cliffed(CREATED_AT).unwrap());
+        assert!(!s.has_cliffed(CREATED_AT + 10 * DAY).unwrap());
+        assert!(s.has_cliffed(CREATED_AT + 10 * DAY + 1).unwrap());
+
+        let no_cliff = VestingSchedule { cliff_days: 0, ..schedule() };
+        assert!(no_cliff.has_cliffed(CREATED_AT).unwrap());
+    }
+
+    #[test]
+    fn unlocks_linearly_up_to_amount() {
+        let s = schedule();
+        assert_eq!(s.unlocked(CREATED_AT).unwrap(), 0);
+        assert_eq!(s.unlocked(CREATED_AT + DAY - 1).unwrap(), 9);
+        assert_eq!(s.unlocked(CREATED_AT + 50 * DAY).unwrap(), 500);
+        assert_eq!(s.unlocked(CREATED_AT + 100 * DAY - 1).unwrap(), 999);
+        assert_eq!(s.unlocked(CREATED_AT + 100 * DAY).unwrap(), 1000);
+        assert_eq!(s.unlocked(CREATED_AT + 1000 * DAY).unwrap(), 1000);
+
+        let bonus = VestingSchedule { bonus_unlocked: 100, ..schedule() };
+        assert_eq!(bonus.unlocked(CREATED_AT).unwrap(), 100);
+        assert_eq!(bonus.unlocked(CREATED_AT + 95 * DAY).unwrap(), 1000);
+    }
+
+    #[test]
+    fn nothing_but_bonus_vests_before_the_cliff() {
+        let s = VestingSchedule { bonus_unlocked: 7, ..schedule() };
+        assert_eq!(s.vested(CREATED_AT + 10 * DAY).unwrap(), 7);
+        // The cliff releases everything the linear schedule accrued during it
+        assert_eq!(s.vested(CREATED_AT + 10 * DAY + 1).unwrap(), 107);
+
+        let huge_bonus = VestingSchedule { bonus_unlocked: 5000, ..schedule() };
+        assert_eq!(huge_bonus.vested(CREATED_AT).unwrap(), 1000);
+    }
+
+    #[test]
+    fn available_is_vested_less_claimed_capped_by_balance() {
+        let now = CREATED_AT + 50 * DAY;
+        assert_eq!(schedule().available(now).unwrap(), 500);
+
+        let claimed = VestingSchedule { claimed: 300, balance: 700, ..schedule() };
+        assert_eq!(claimed.available(now).unwrap(), 200);
+
+        let short_vault = VestingSchedule { balance: 100, ..schedule() };
+        assert_eq!(short_vault.available(now).unwrap(), 100);
+
+        let claimed_ahead = VestingSchedule { claimed: 600, balance: 400, ..schedule() };
+        assert_eq!(claimed_ahead.available(now).unwrap(), 0);
+    }
+
+    #[test]
+    fn paused_time_does_not_vest() {
+        let paused = VestingSchedule { is_paused: true, paused_at: CREATED_AT + 20 * DAY, ..schedule() };
+        assert_eq!(paused.vested(CREATED_AT + 80 * DAY).unwrap(), 200);
+
+        let resumed = VestingSchedule { total_paused: 10 * DAY, ..schedule() };
+        assert_eq!(resumed.vested(CREATED_AT + 60 * DAY).unwrap(), 500);
+        assert_eq!(resumed.vested(CREATED_AT + 110 * DAY).unwrap(), 1000);
+    }
+
+    #[test]
+    fn status_reports_the_next_unlock() {
+        let before_cliff = schedule().status(CREATED_AT + DAY).unwrap();
+        assert_eq!(before_cliff.vested, 0);
+        assert_eq!(before_cliff.locked, 1000);
+        assert_eq!(before_cliff.next_unlock_at, (CREATED_AT + 10 * DAY + 1) as u64);
+        assert_eq!(before_cliff.fully_vested_at, (CREATED_AT + 100 * DAY) as u64);
+
+        // One token unlocks every 8640 seconds
+        let half_way = schedule().status(CREATED_AT + 50 * DAY).unwrap();
+        assert_eq!(half_way.claimable, 500);
+        assert_eq!(half_way.next_unlock_at, (CREATED_AT + 50 * DAY + 8640) as u64);
+
+        let done = schedule().status(CREATED_AT + 100 * DAY).unwrap();
+        assert_eq!(done.next_unlock_at, 0);
+        assert_eq!(done.locked, 0);
+    }
+
+    #[test]
+    fn status_of_a_usd_ticket_reports_what_is_owed_in_usd() {
+        let usd = VestingSchedule { usd_amount: 2_000_000, usd_paid: 400_000, ..schedule() };
+
+        let half_way = usd.status(CREATED_AT + 50 * DAY).unwrap();
+        assert_eq!(half_way.claimable, 0);
+        assert_eq!(half_way.usd_claimable, 600_000);
+
+        assert_eq!(schedule().status(CREATED_AT + 50 * DAY).unwrap().usd_claimable, 0);
+        let revoked = VestingSchedule { is_revoked: true, ..usd };
+        assert_eq!(revoked.status(CREATED_AT + 50 * DAY).unwrap().usd_claimable, 0);
+    }
+
+    #[test]
+    fn early_release_pays_out_the_whole_balance() {
+        let mut released = VestingSchedule { claimed: 100, balance: 900, ..schedule() };
+        assert_eq!(released.release_early(), Ok(900));
+        assert_eq!((released.claimed, released.balance), (1000, 0));
+
+        let mut revoked = VestingSchedule { is_revoked: true, ..schedule() };
+        assert_eq!(revoked.release_early(), Err(ScheduleError::Revoked));
+    }
+
+    #[test]
+    fn usd_claim_records_what_the_vault_could_not_pay() {
+        // $1 of a $2 grant has vested half way; each token base unit is worth 1000 micro-dollars
+        let to_tokens = |usd: u64| Ok::<_, ScheduleError>(usd / 1000);
+        let to_usd = |tokens: u64| Ok::<_, ScheduleError>(tokens * 1000);
+        let usd = VestingSchedule { usd_amount: 2_000_000, ..schedule() };
+
+        let mut paid = usd;
+        let payout = paid.claim_usd(CREATED_AT + 50 * DAY, 1000, to_tokens, to_usd).unwrap();
+        assert_eq!(payout, UsdPayout { tokens: 1000, usd: 1_000_000, returned: 0 });
+        assert_eq!((paid.usd_paid, paid.usd_unpaid, paid.claimed, paid.balance), (1_000_000, 0, 1000, 0));
+
+        let mut short = usd;
+        let payout = short.claim_usd(CREATED_AT + 50 * DAY, 400, to_tokens, to_usd).unwrap();
+        assert_eq!(payout, UsdPayout { tokens: 400, usd: 400_000, returned: 0 });
+        assert_eq!((short.usd_paid, short.usd_unpaid, short.balance), (400_000, 600_000, 600));
+    }
+
+    #[test]
+    fn settling_a_usd_grant_returns_the_leftover_balance() {
+        // A $1 grant at 100 micro-dollars per base unit only needs 10_000 of the vault's tokens
+        let to_tokens = |usd: u64| Ok::<_, ScheduleError>(usd / 100);
+        let to_usd = |tokens: u64| Ok::<_, ScheduleError>(tokens * 100);
+        let usd = VestingSchedule { amount: 50_000, balance: 50_000, usd_amount: 1_000_000, ..schedule() };
+
+        let mut half_way = usd;
+        let payout = half_way.claim_usd(CREATED_AT + 50 * DAY, 50_000, to_tokens, to_usd).unwrap();
+        assert_eq!(payout, UsdPayout { tokens: 5_000, usd: 500_000, returned: 0 });
+        assert!(!half_way.is_usd_settled());
+
+        let payout = half_way.claim_usd(CREATED_AT + 100 * DAY, 45_000, to_tokens, to_usd).unwrap();
+        assert_eq!(payout, UsdPayout { tokens: 5_000, usd: 500_000, returned: 40_000 });
+        assert!(half_way.is_usd_settled());
+        assert_eq!((half_way.claimed, half_way.balance), (10_000, 0));
+    }
+
+    #[test]
+    fn a_pause_stops_the_clock_until_resumed() {
+        let mut s = schedule();
+        s.pause(CREATED_AT + 30 * DAY).unwrap();
+        assert_eq!(s.pause(CREATED_AT + 31 * DAY), Err(ScheduleError::Paused));
+        assert_eq!(s.vested(CREATED_AT + 60 * DAY).unwrap(), 300);
+
+        assert_eq!(s.resume(CREATED_AT + 60 * DAY), Ok(30 * DAY));
+        assert_eq!(s.vested(CREATED_AT + 60 * DAY).unwrap(), 300);
+        assert_eq!(s.vested(CREATED_AT + 60 * DAY + 8640).unwrap(), 301);
+        assert_eq!(s.resume(CREATED_AT + 61 * DAY), Err(ScheduleError::NotPaused));
+    }
+
+    #[test]
+    fn clock_before_creation_and_overflow_are_errors() {
+        assert_eq!(schedule().vested(CREATED_AT - 1), Err(ScheduleError::ClockBeforeStart));
+
+        let long_cliff = VestingSchedule { cliff_days: u64::MAX, ..schedule() };
+        assert_eq!(long_cliff.has_cliffed(CREATED_AT), Err(ScheduleError::Overflow));
+
+        let long_vesting = VestingSchedule { vesting_days: u64::MAX, cliff_days: 0, ..schedule() };
+        assert_eq!(long_vesting.unlocked(CREATED_AT), Err(ScheduleError::Overflow));
+    }
+}
@@ -0,0 +1,1938 @@
+// Integration tests against an in-process bank. The program runs natively next to a mock
+// Chainlink store and a hand-built Pyth price account, and the clock is set directly, so
+// the whole suite runs offline in a few seconds.
+use anchor_lang::prelude::{AnchorDeserialize, AnchorSerialize, Pubkey};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use pyth_client::{AccountType, Price, PriceStatus, MAGIC, VERSION_2};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::{AccountSharedData, WritableAccount};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::clock::Clock;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program::set_return_data;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::transport::TransportError;
+use solana_sdk::{system_instruction, system_program};
+
+use vestor_using_anchor_chainlink_master::merkle;
+use vestor_using_anchor_chainlink_master::schedule::SECONDS_PER_DAY;
+use vestor_using_anchor_chainlink_master::{
+    accounts, instruction, ErrorCode, MerklePool, OracleRegistry, PoolPosition, Share, SharedTicket, Ticket,
+    CHAINLINK_VALUE_SEED, MERKLE_POOL_CLAWBACK_GRACE, MERKLE_POOL_SEED, MERKLE_POSITION_SEED, MINT_PRICE_FEED_SEED,
+    SHARED_TICKET_SEED,
+};
+
+
+const START: i64 = 1_650_000_000;
+const DEPOSIT: u64 = 10_000_000;
+const AMOUNT: u64 = 1_000_000;
+const CLIFF_DAYS: u64 = 10;
+const VESTING_DAYS: u64 = 100;
+
+// $100 SOL, $3000 ETH and $2 for the test mint, Chainlink-style 8 decimals
+const SOL_PRICE: i128 = 10_000_000_000;
+const ETH_PRICE: i128 = 300_000_000_000;
+const TOKEN_PRICE: i128 = 200_000_000;
+
+
+// ---------------------------------------------------------------------------------------
+// Mock Chainlink store. Feed accounts hold a `MockFeed`, and the store answers the
+// `query` instruction of `chainlink_solana` through return data, like the real one does.
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+struct MockRound {
+    round_id: u32,
+    slot: u64,
+    timestamp: u32,
+    answer: i128,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+struct MockFeed {
+    round: MockRound,
+    decimals: u8,
+    description: String,
+}
+
+// Scope variants of the store's query instruction
+const QUERY_DECIMALS: u8 = 1;
+const QUERY_DESCRIPTION: u8 = 2;
+const QUERY_LATEST_ROUND_DATA: u8 = 4;
+
+fn process_chainlink(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let feed = MockFeed::deserialize(&mut &accounts[0].data.borrow()[..])?;
+
+    let answer = match data.get(8) {
+        Some(&QUERY_DECIMALS) => feed.decimals.try_to_vec()?,
+        Some(&QUERY_DESCRIPTION) => feed.description.try_to_vec()?,
+        Some(&QUERY_LATEST_ROUND_DATA) => feed.round.try_to_vec()?,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    set_return_data(&answer);
+
+    Ok(())
+}
+
+
+fn pyth_price(price: i64, expo: i32, pub_slot: u64) -> Price {
+    let mut account: Price = bytemuck::Zeroable::zeroed();
+    account.magic = MAGIC;
+    account.ver = VERSION_2;
+    account.atype = AccountType::Price as u32;
+    account.expo = expo;
+    account.agg.price = price;
+    account.agg.conf = 0;
+    account.agg.status = PriceStatus::Trading;
+    account.agg.pub_slot = pub_slot;
+    account
+}
+
+
+// ---------------------------------------------------------------------------------------
+
+struct Env {
+    ctx: ProgramTestContext,
+    mint: Keypair,
+    vestor: Keypair,
+    contract_owner_deposit: Keypair,
+    ticket_creator_deposit: Keypair,
+    randomness_feed: Keypair,
+    // The feed registered on the vestor, the default key until `init_randomness_feed` runs
+    registered_randomness_feed: Pubkey,
+    chainlink_program: Pubkey,
+    sol_feed: Pubkey,
+    eth_feed: Pubkey,
+    pyth_program: Pubkey,
+    pyth_sol_price: Pubkey,
+}
+
+struct TestTicket {
+    ticket: Keypair,
+    signer: Pubkey,
+    vault: Keypair,
+    claimant: Keypair,
+    claimant_vault: Keypair,
+    // What `set_usd_terms` stored on the ticket, the default key for token grants
+    usd_price_feed: Pubkey,
+}
+
+struct TestSharedTicket {
+    shared_ticket: Keypair,
+    signer: Pubkey,
+    vault: Keypair,
+    // Each claimant and their token account, in share order
+    claimants: Vec<(Keypair, Keypair)>,
+}
+
+struct TestMerklePool {
+    pool: Keypair,
+    signer: Pubkey,
+    vault: Keypair,
+    // Each claimant and their token account, in leaf order
+    claimants: Vec<(Keypair, Keypair)>,
+    amounts: Vec<u64>,
+    leaves: Vec<[u8; 32]>,
+}
+
+fn program_id() -> Pubkey {
+    vestor_using_anchor_chainlink_master::id()
+}
+
+fn assert_error(result: Result<(), TransportError>, expected: u32) {
+    match result.unwrap_err() {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => assert_eq!(code, expected),
+        e => panic!("expected custom error {}, got {:?}", expected, e),
+    }
+}
+
+impl Env {
+    async fn new() -> Env {
+        let chainlink_program = Pubkey::new_unique();
+        let pyth_program = Pubkey::new_unique();
+
+        let mut test = ProgramTest::new(
+            "vestor_using_anchor_chainlink_master",
+            program_id(),
+            processor!(vestor_using_anchor_chainlink_master::entry),
+        );
+        test.add_program("chainlink_store", chainlink_program, processor!(process_chainlink));
+
+        let mut env = Env {
+            ctx: test.start_with_context().await,
+            mint: Keypair::new(),
+            vestor: Keypair::new(),
+            contract_owner_deposit: Keypair::new(),
+            ticket_creator_deposit: Keypair::new(),
+            randomness_feed: Keypair::new(),
+            registered_randomness_feed: Pubkey::default(),
+            chainlink_program,
+            sol_feed: Pubkey::new_unique(),
+            eth_feed: Pubkey::new_unique(),
+            pyth_program,
+            pyth_sol_price: Pubkey::new_unique(),
+        };
+
+        env.warp_to(START).await;
+        env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+        env
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.ctx.payer.pubkey()
+    }
+
+    async fn clock(&mut self) -> Clock {
+        self.ctx.banks_client.get_sysvar::<Clock>().await.unwrap()
+    }
+
+    // Moves to a later slot (so the same transaction can be sent again) with the given time
+    async fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.clock().await;
+        self.ctx.warp_to_slot(clock.slot + 100).unwrap();
+
+        clock.slot += 100;
+        clock.unix_timestamp = unix_timestamp;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    async fn warp_days(&mut self, days: u64) {
+        let now = self.clock().await.unix_timestamp;
+        self.warp_to(now + (days * SECONDS_PER_DAY) as i64).await;
+    }
+
+    // Writes fresh Chainlink rounds and a fresh Pyth price at the current time and slot
+    async fn publish_prices(&mut self, sol: i128, eth: i128) {
+        let clock = self.clock().await;
+
+        self.set_chainlink_feed(self.sol_feed, sol, "SOL / USD").await;
+        self.set_chainlink_feed(self.eth_feed, eth, "ETH / USD").await;
+
+        let price = pyth_price(sol as i64, -8, clock.slot);
+        self.set_account(self.pyth_sol_price, self.pyth_program, bytemuck::bytes_of(&price).to_vec());
+    }
+
+    // A fresh round at the current time and slot on the mock Chainlink store
+    async fn set_chainlink_feed(&mut self, feed: Pubkey, answer: i128, description: &str) {
+        let clock = self.clock().await;
+        let data = MockFeed {
+            round: MockRound {
+                round_id: 1,
+                slot: clock.slot,
+                timestamp: clock.unix_timestamp as u32,
+                answer,
+            },
+            decimals: 8,
+            description: description.to_string(),
+        }
+        .try_to_vec()
+        .unwrap();
+        self.set_account(feed, self.chainlink_program, data);
+    }
+
+    fn set_account(&mut self, address: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let mut account = AccountSharedData::new(1_000_000_000, data.len(), &owner);
+        account.data_as_mut_slice().copy_from_slice(&data);
+        self.ctx.set_account(&address, &account);
+    }
+
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransportError> {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.payer()), &all_signers, blockhash);
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    async fn account<T: AccountDeserialize>(&mut self, address: Pubkey) -> Option<T> {
+        let account = self.ctx.banks_client.get_account(address).await.unwrap()?;
+        Some(T::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        let account = self.ctx.banks_client.get_account(address).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    async fn token_owner(&mut self, address: Pubkey) -> Pubkey {
+        let account = self.ctx.banks_client.get_account(address).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().owner
+    }
+
+    async fn fund(&mut self, to: Pubkey, lamports: u64) {
+        let ix = system_instruction::transfer(&self.payer(), &to, lamports);
+        self.send(&[ix], &[]).await.unwrap();
+    }
+
+    async fn create_account_ix(&mut self, account: &Pubkey, space: usize, owner: &Pubkey) -> Instruction {
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        system_instruction::create_account(&self.payer(), account, rent.minimum_balance(space), space as u64, owner)
+    }
+
+    async fn create_mint(&mut self) {
+        let mint = self.mint.pubkey();
+        let create = self.create_account_ix(&mint, spl_token::state::Mint::LEN, &spl_token::id()).await;
+        let init = spl_token::instruction::initialize_mint(&spl_token::id(), &mint, &self.payer(), None, 6).unwrap();
+
+        let mint_keypair = Keypair::from_bytes(&self.mint.to_bytes()).unwrap();
+        self.send(&[create, init], &[&mint_keypair]).await.unwrap();
+    }
+
+    async fn create_token_account(&mut self, account: &Keypair, owner: &Pubkey) {
+        let create = self
+            .create_account_ix(&account.pubkey(), spl_token::state::Account::LEN, &spl_token::id())
+            .await;
+        let init = spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &account.pubkey(),
+            &self.mint.pubkey(),
+            owner,
+        )
+        .unwrap();
+        self.send(&[create, init], &[account]).await.unwrap();
+    }
+
+    // Mint, deposit accounts, `initialize`, oracle registry and randomness feed
+    async fn initialize(&mut self) -> Result<(), TransportError> {
+        self.initialize_without_randomness().await?;
+
+        let payer = self.payer();
+        let init_randomness_feed = Instruction {
+            program_id: program_id(),
+            accounts: accounts::InitRandomnessFeed {
+                randomness_feed: self.randomness_feed.pubkey(),
+                vestor: self.vestor.pubkey(),
+                randomness_authority: payer,
+                authority: payer,
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::InitRandomnessFeed {}.data(),
+        };
+        let randomness_feed = Keypair::from_bytes(&self.randomness_feed.to_bytes()).unwrap();
+        self.send(&[init_randomness_feed], &[&randomness_feed]).await?;
+
+        self.registered_randomness_feed = randomness_feed.pubkey();
+        Ok(())
+    }
+
+    async fn initialize_without_randomness(&mut self) -> Result<(), TransportError> {
+        self.initialize_vestor().await?;
+
+        let payer = self.payer();
+        let set_oracle_registry = Instruction {
+            program_id: program_id(),
+            accounts: accounts::ConfigureVestor { vestor: self.vestor.pubkey(), authority: payer }.to_account_metas(None),
+            data: instruction::SetOracleRegistry {
+                oracles: OracleRegistry {
+                    chainlink_program: self.chainlink_program,
+                    chainlink_sol_feed: self.sol_feed,
+                    chainlink_eth_feed: self.eth_feed,
+                    pyth_program: self.pyth_program,
+                    pyth_sol_price: self.pyth_sol_price,
+                },
+            }
+            .data(),
+        };
+        self.send(&[set_oracle_registry], &[]).await
+    }
+
+    // Mint, deposit accounts and `initialize`, leaving the vestor on its default oracles
+    async fn initialize_vestor(&mut self) -> Result<(), TransportError> {
+        self.create_mint().await;
+
+        let payer = self.payer();
+        let contract_owner_deposit = Keypair::from_bytes(&self.contract_owner_deposit.to_bytes()).unwrap();
+        let ticket_creator_deposit = Keypair::from_bytes(&self.ticket_creator_deposit.to_bytes()).unwrap();
+        self.create_token_account(&contract_owner_deposit, &payer).await;
+        self.create_token_account(&ticket_creator_deposit, &payer).await;
+
+        let mint_to = spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &self.mint.pubkey(),
+            &contract_owner_deposit.pubkey(),
+            &payer,
+            &[],
+            DEPOSIT,
+        )
+        .unwrap();
+        self.send(&[mint_to], &[]).await.unwrap();
+
+        let initialize = Instruction {
+            program_id: program_id(),
+            accounts: accounts::Initialize {
+                vestor: self.vestor.pubkey(),
+                contract_owner_deposit_token_vault: contract_owner_deposit.pubkey(),
+                ticket_creator_deposit_token_vault: ticket_creator_deposit.pubkey(),
+                token_mint: self.mint.pubkey(),
+                owner: payer,
+                token_program: spl_token::id(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::Initialize { amount: DEPOSIT }.data(),
+        };
+        let vestor = Keypair::from_bytes(&self.vestor.to_bytes()).unwrap();
+        self.send(&[initialize], &[&vestor]).await
+    }
+
+    async fn tickets_issued(&mut self) -> u8 {
+        let account = self.ctx.banks_client.get_account(self.vestor.pubkey()).await.unwrap().unwrap();
+        // `tickets_issued` is the first field after the discriminator
+        account.data[8]
+    }
+
+    async fn create_ticket(&mut self, cliff: u64, vesting: u64, amount: u64, irrevocable: bool) -> Result<TestTicket, TransportError> {
+        let ticket = Keypair::new();
+        let seed = self.tickets_issued().await.to_string();
+        let (signer, bump) = Pubkey::find_program_address(&[ticket.pubkey().as_ref(), seed.as_bytes()], &program_id());
+
+        let test_ticket = TestTicket {
+            ticket,
+            signer,
+            vault: Keypair::new(),
+            claimant: Keypair::new(),
+            claimant_vault: Keypair::new(),
+            usd_price_feed: Pubkey::default(),
+        };
+        self.fund(test_ticket.claimant.pubkey(), 1_000_000_000).await;
+        self.create_token_account(&test_ticket.vault, &signer).await;
+        self.create_token_account(&test_ticket.claimant_vault, &test_ticket.claimant.pubkey()).await;
+
+        let space = 8 + std::mem::size_of::<Ticket>();
+        let create = self.create_account_ix(&test_ticket.ticket.pubkey(), space, &program_id()).await;
+        let create_ticket = Instruction {
+            program_id: program_id(),
+            accounts: accounts::CreateTicket {
+                ticket: test_ticket.ticket.pubkey(),
+                owner: self.payer(),
+                signer,
+                token_mint: self.mint.pubkey(),
+                ticket_creator_deposit_token_vault: self.ticket_creator_deposit.pubkey(),
+                claimant_receive_token_vault: test_ticket.claimant_vault.pubkey(),
+                vault: test_ticket.vault.pubkey(),
+                token_program: spl_token::id(),
+                vestor: self.vestor.pubkey(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::CreateTicket {
+                beneficiary: test_ticket.claimant.pubkey(),
+                cliff,
+                vesting,
+                amount,
+                irrevocable,
+                bump,
+            }
+            .data(),
+        };
+
+        self.send(&[create, create_ticket], &[&test_ticket.ticket]).await?;
+        Ok(test_ticket)
+    }
+
+    fn claim_ix(&self, ticket: &TestTicket, claimant: &Pubkey, sol_feed: Pubkey) -> Instruction {
+        let vestor = self.vestor.pubkey();
+        let value_pda = |feed: &Pubkey| {
+            Pubkey::find_program_address(&[CHAINLINK_VALUE_SEED, vestor.as_ref(), feed.as_ref()], &program_id()).0
+        };
+
+        Instruction {
+            program_id: program_id(),
+            accounts: accounts::Claim {
+                signer: ticket.signer,
+                ticket: ticket.ticket.pubkey(),
+                vestor,
+                token_mint: self.mint.pubkey(),
+                pda_deposit_token_vault: ticket.vault.pubkey(),
+                claimant_receive_token_vault: ticket.claimant_vault.pubkey(),
+                claimant: *claimant,
+                randomness_feed: self.registered_randomness_feed,
+                chainlink_sol_feed: sol_feed,
+                chainlink_eth_feed: self.eth_feed,
+                chainlink_program: self.chainlink_program,
+                pyth_account: self.pyth_sol_price,
+                usd_price_feed: ticket.usd_price_feed,
+                system_program: system_program::id(),
+                chainlink_sol_value: value_pda(&sol_feed),
+                chainlink_eth_value: value_pda(&self.eth_feed),
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::Claim {}.data(),
+        }
+    }
+
+    async fn claim(&mut self, ticket: &TestTicket) -> Result<(), TransportError> {
+        let ix = self.claim_ix(ticket, &ticket.claimant.pubkey(), self.sol_feed);
+        self.send(&[ix], &[&ticket.claimant]).await
+    }
+
+    async fn request_early_release(&mut self, ticket: &TestTicket) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::RequestEarlyRelease {
+                ticket: ticket.ticket.pubkey(),
+                vestor: self.vestor.pubkey(),
+                randomness_feed: self.randomness_feed.pubkey(),
+                claimant: ticket.claimant.pubkey(),
+            }
+            .to_account_metas(None),
+            data: instruction::RequestEarlyRelease {}.data(),
+        };
+        self.send(&[ix], &[&ticket.claimant]).await
+    }
+
+    // Signed by the payer, which `initialize` registers as the randomness authority
+    async fn publish_randomness(&mut self, value: [u8; 32]) {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::PublishRandomness {
+                randomness_feed: self.randomness_feed.pubkey(),
+                randomness_authority: self.payer(),
+            }
+            .to_account_metas(None),
+            data: instruction::PublishRandomness { value }.data(),
+        };
+        self.send(&[ix], &[]).await.unwrap();
+    }
+
+    // Registers `feed` as the vestor's price feed for the test mint
+    async fn set_mint_price_feed(&mut self, feed: Pubkey) {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::SetMintPriceFeed {
+                mint_price_feed: self.mint_price_feed(),
+                vestor: self.vestor.pubkey(),
+                token_mint: self.mint.pubkey(),
+                price_feed: feed,
+                authority: self.payer(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::SetMintPriceFeed {}.data(),
+        };
+        self.send(&[ix], &[]).await.unwrap();
+    }
+
+    fn mint_price_feed(&self) -> Pubkey {
+        let seeds = [MINT_PRICE_FEED_SEED, self.vestor.pubkey().as_ref(), self.mint.pubkey().as_ref()];
+        Pubkey::find_program_address(&seeds, &program_id()).0
+    }
+
+    // Signed by the payer as the ticket owner
+    async fn pause_ticket(&mut self, ticket: &TestTicket) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::PauseTicket { ticket: ticket.ticket.pubkey(), owner: self.payer() }
+                .to_account_metas(None),
+            data: instruction::PauseTicket {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    // Signed by the payer as the ticket owner
+    async fn resume_ticket(&mut self, ticket: &TestTicket) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::PauseTicket { ticket: ticket.ticket.pubkey(), owner: self.payer() }
+                .to_account_metas(None),
+            data: instruction::ResumeTicket {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    // Signed by the payer as the ticket owner
+    async fn set_acceleration(&mut self, ticket: &TestTicket, acceleration_bps: u16, double_trigger: bool) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::SetAcceleration { ticket: ticket.ticket.pubkey(), owner: self.payer() }
+                .to_account_metas(None),
+            data: instruction::SetAcceleration { acceleration_bps, double_trigger }.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    fn set_change_of_control_ix(&self, authority: Pubkey, change_of_control: bool) -> Instruction {
+        Instruction {
+            program_id: program_id(),
+            accounts: accounts::SetChangeOfControl { vestor: self.vestor.pubkey(), authority }.to_account_metas(None),
+            data: instruction::SetChangeOfControl { change_of_control }.data(),
+        }
+    }
+
+    fn terminate_ticket_ix(&self, ticket: &TestTicket, owner: Pubkey) -> Instruction {
+        Instruction {
+            program_id: program_id(),
+            accounts: accounts::TerminateTicket { ticket: ticket.ticket.pubkey(), owner }.to_account_metas(None),
+            data: instruction::TerminateTicket {}.data(),
+        }
+    }
+
+    // Signed by the payer as the ticket owner
+    async fn accelerate(&mut self, ticket: &TestTicket, bps: u16) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::Accelerate {
+                ticket: ticket.ticket.pubkey(),
+                vestor: self.vestor.pubkey(),
+                owner: self.payer(),
+            }
+            .to_account_metas(None),
+            data: instruction::Accelerate { bps }.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    // Signed by the payer as the ticket owner and by `claimant`
+    fn amend_schedule_ix(&self, ticket: &TestTicket, claimant: Pubkey, cliff: u64, vesting: u64) -> Instruction {
+        Instruction {
+            program_id: program_id(),
+            accounts: accounts::AmendSchedule { ticket: ticket.ticket.pubkey(), owner: self.payer(), claimant }
+                .to_account_metas(None),
+            data: instruction::AmendSchedule { cliff, vesting }.data(),
+        }
+    }
+
+    // Signed by the payer as the ticket owner and by the claimant
+    fn set_usd_terms_ix(&self, ticket: &TestTicket, usd_amount: u64) -> Instruction {
+        Instruction {
+            program_id: program_id(),
+            accounts: accounts::SetUsdTerms {
+                ticket: ticket.ticket.pubkey(),
+                mint_price_feed: self.mint_price_feed(),
+                owner: self.payer(),
+                claimant: ticket.claimant.pubkey(),
+            }
+            .to_account_metas(None),
+            data: instruction::SetUsdTerms { usd_amount }.data(),
+        }
+    }
+
+    // A tranche releasing `release_bps` of the ticket once `feed` is at or above `strike` whole
+    // dollars. TWAP tranches pass the feed's price history as a remaining account.
+    async fn add_price_tranche(
+        &mut self,
+        ticket: &TestTicket,
+        feed: Pubkey,
+        strike: i128,
+        release_bps: u16,
+        twap_window: u64,
+        price_history: Option<Pubkey>,
+    ) -> Result<Pubkey, TransportError> {
+        let tranche = Keypair::new();
+        let mut metas = accounts::AddPriceTranche {
+            price_tranche: tranche.pubkey(),
+            ticket: ticket.ticket.pubkey(),
+            owner: self.payer(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None);
+        metas.extend(price_history.map(|history| AccountMeta::new_readonly(history, false)));
+
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: metas,
+            data: instruction::AddPriceTranche {
+                feed,
+                strike,
+                strike_decimals: 0,
+                release_bps,
+                hold_seconds: 0,
+                twap_window,
+            }
+            .data(),
+        };
+        self.send(&[ix], &[&tranche]).await?;
+        Ok(tranche.pubkey())
+    }
+
+    async fn init_price_history(&mut self, feed: Pubkey, min_interval: u64) -> Result<Pubkey, TransportError> {
+        let history = Keypair::new();
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::InitPriceHistory {
+                price_history: history.pubkey(),
+                vestor: self.vestor.pubkey(),
+                authority: self.payer(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::InitPriceHistory { feed, expo: -8, min_interval }.data(),
+        };
+        self.send(&[ix], &[&history]).await?;
+        Ok(history.pubkey())
+    }
+
+    fn revoke_ix(&self, ticket: &TestTicket, ticket_creator: Pubkey) -> Instruction {
+        let metas = accounts::Revoke {
+            signer: ticket.signer,
+            vestor: self.vestor.pubkey(),
+            ticket_creator,
+            ticket: ticket.ticket.pubkey(),
+            token_mint: self.mint.pubkey(),
+            ticket_creator_deposit_token_vault: self.ticket_creator_deposit.pubkey(),
+            pda_deposit_token_vault: ticket.vault.pubkey(),
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None);
+
+        Instruction { program_id: program_id(), accounts: metas, data: instruction::Revoke {}.data() }
+    }
+
+    async fn create_shared_ticket(&mut self, shares_bps: &[u16], amount: u64, irrevocable: bool) -> Result<TestSharedTicket, TransportError> {
+        let shared_ticket = Keypair::new();
+        let (signer, bump) =
+            Pubkey::find_program_address(&[SHARED_TICKET_SEED, shared_ticket.pubkey().as_ref()], &program_id());
+
+        let test_ticket = TestSharedTicket {
+            shared_ticket,
+            signer,
+            vault: Keypair::new(),
+            claimants: shares_bps.iter().map(|_| (Keypair::new(), Keypair::new())).collect(),
+        };
+        self.create_token_account(&test_ticket.vault, &signer).await;
+        for (claimant, token_account) in &test_ticket.claimants {
+            self.create_token_account(token_account, &claimant.pubkey()).await;
+        }
+
+        let shares = test_ticket
+            .claimants
+            .iter()
+            .zip(shares_bps)
+            .map(|((claimant, _), &share_bps)| Share { claimant: claimant.pubkey(), share_bps })
+            .collect();
+        let create_shared_ticket = Instruction {
+            program_id: program_id(),
+            accounts: accounts::CreateSharedTicket {
+                shared_ticket: test_ticket.shared_ticket.pubkey(),
+                signer,
+                vestor: self.vestor.pubkey(),
+                owner: self.payer(),
+                token_mint: self.mint.pubkey(),
+                ticket_creator_deposit_token_vault: self.ticket_creator_deposit.pubkey(),
+                vault: test_ticket.vault.pubkey(),
+                token_program: spl_token::id(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::CreateSharedTicket {
+                shares,
+                cliff: 0,
+                vesting: VESTING_DAYS,
+                amount,
+                irrevocable,
+                bump,
+            }
+            .data(),
+        };
+
+        self.send(&[create_shared_ticket], &[&test_ticket.shared_ticket]).await?;
+        Ok(test_ticket)
+    }
+
+    async fn claim_shared(&mut self, ticket: &TestSharedTicket, claimant: &Keypair, token_account: Pubkey) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::ClaimShared {
+                shared_ticket: ticket.shared_ticket.pubkey(),
+                signer: ticket.signer,
+                claimant: claimant.pubkey(),
+                claimant_token_account: token_account,
+                vault: ticket.vault.pubkey(),
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::ClaimShared {}.data(),
+        };
+        self.send(&[ix], &[claimant]).await
+    }
+
+    async fn revoke_shared(&mut self, ticket: &TestSharedTicket) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::RevokeShared {
+                shared_ticket: ticket.shared_ticket.pubkey(),
+                signer: ticket.signer,
+                owner: self.payer(),
+                ticket_creator_deposit_token_vault: self.ticket_creator_deposit.pubkey(),
+                vault: ticket.vault.pubkey(),
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::RevokeShared {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    // One leaf per amount, each for a new funded claimant, vesting from now with no cliff
+    async fn create_merkle_pool(&mut self, amounts: &[u64], total_amount: u64) -> Result<TestMerklePool, TransportError> {
+        let pool = Keypair::new();
+        let (signer, bump) = Pubkey::find_program_address(&[MERKLE_POOL_SEED, pool.pubkey().as_ref()], &program_id());
+
+        let test_pool = TestMerklePool {
+            pool,
+            signer,
+            vault: Keypair::new(),
+            claimants: amounts.iter().map(|_| (Keypair::new(), Keypair::new())).collect(),
+            amounts: amounts.to_vec(),
+            leaves: Vec::new(),
+        };
+        self.create_token_account(&test_pool.vault, &signer).await;
+        for (claimant, token_account) in &test_pool.claimants {
+            self.create_token_account(token_account, &claimant.pubkey()).await;
+            self.fund(claimant.pubkey(), 1_000_000_000).await;
+        }
+        let leaves: Vec<[u8; 32]> = test_pool
+            .claimants
+            .iter()
+            .zip(amounts)
+            .enumerate()
+            .map(|(index, ((claimant, _), &amount))| merkle::leaf(index as u64, &claimant.pubkey(), amount))
+            .collect();
+
+        let create_merkle_pool = Instruction {
+            program_id: program_id(),
+            accounts: accounts::CreateMerklePool {
+                pool: test_pool.pool.pubkey(),
+                pool_signer: signer,
+                vestor: self.vestor.pubkey(),
+                authority: self.payer(),
+                token_mint: self.mint.pubkey(),
+                authority_deposit_token_vault: self.ticket_creator_deposit.pubkey(),
+                vault: test_pool.vault.pubkey(),
+                token_program: spl_token::id(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::CreateMerklePool {
+                root: merkle::root(&leaves),
+                total_amount,
+                cliff: 0,
+                vesting: VESTING_DAYS,
+                bump,
+            }
+            .data(),
+        };
+
+        self.send(&[create_merkle_pool], &[&test_pool.pool]).await?;
+        Ok(TestMerklePool { leaves, ..test_pool })
+    }
+
+    fn position(&self, pool: &TestMerklePool, index: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[MERKLE_POSITION_SEED, pool.pool.pubkey().as_ref(), &index.to_le_bytes()],
+            &program_id(),
+        )
+        .0
+    }
+
+    // `claimant` claims leaf `index` with that leaf's amount and proof
+    async fn claim_from_pool(&mut self, pool: &TestMerklePool, index: usize, claimant: &Keypair, token_account: Pubkey) -> Result<(), TransportError> {
+        self.claim_from_pool_with_proof(pool, index, claimant, token_account, merkle::proof(&pool.leaves, index))
+            .await
+    }
+
+    async fn claim_from_pool_with_proof(
+        &mut self,
+        pool: &TestMerklePool,
+        index: usize,
+        claimant: &Keypair,
+        token_account: Pubkey,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::ClaimFromPool {
+                pool: pool.pool.pubkey(),
+                pool_signer: pool.signer,
+                position: self.position(pool, index as u64),
+                claimant: claimant.pubkey(),
+                claimant_token_account: token_account,
+                vault: pool.vault.pubkey(),
+                token_program: spl_token::id(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::ClaimFromPool { index: index as u64, amount: pool.amounts[index], proof }.data(),
+        };
+        self.send(&[ix], &[claimant]).await
+    }
+
+    async fn close_merkle_pool(&mut self, pool: &TestMerklePool) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::CloseMerklePool {
+                pool: pool.pool.pubkey(),
+                pool_signer: pool.signer,
+                authority: self.payer(),
+                authority_token_account: self.ticket_creator_deposit.pubkey(),
+                vault: pool.vault.pubkey(),
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::CloseMerklePool {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    // Registers `sol_feed` as the vestor's SOL feed in place of the mock Chainlink one
+    async fn register_sol_feed(&mut self, sol_feed: Pubkey) {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::ConfigureVestor { vestor: self.vestor.pubkey(), authority: self.payer() }
+                .to_account_metas(None),
+            data: instruction::SetOracleRegistry {
+                oracles: OracleRegistry {
+                    chainlink_program: self.chainlink_program,
+                    chainlink_sol_feed: sol_feed,
+                    chainlink_eth_feed: self.eth_feed,
+                    pyth_program: self.pyth_program,
+                    pyth_sol_price: self.pyth_sol_price,
+                },
+            }
+            .data(),
+        };
+        self.send(&[ix], &[]).await.unwrap();
+    }
+}
+
+// Instructions that only exist in `mock-oracle` builds
+#[cfg(feature = "mock-oracle")]
+impl Env {
+    // Signed by the payer as the vestor authority
+    async fn init_mock_price_feed(&mut self) -> Result<Pubkey, TransportError> {
+        let feed = Keypair::new();
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::InitMockPriceFeed {
+                mock_price_feed: feed.pubkey(),
+                vestor: self.vestor.pubkey(),
+                authority: self.payer(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(None),
+            data: instruction::InitMockPriceFeed {}.data(),
+        };
+        self.send(&[ix], &[&feed]).await?;
+        Ok(feed.pubkey())
+    }
+
+    async fn set_mock_price(&mut self, feed: Pubkey, value: i128, expo: i32) {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::SetMockPrice {
+                mock_price_feed: feed,
+                vestor: self.vestor.pubkey(),
+                authority: self.payer(),
+            }
+            .to_account_metas(None),
+            data: instruction::SetMockPrice { value, expo }.data(),
+        };
+        self.send(&[ix], &[]).await.unwrap();
+    }
+
+    async fn check_price_tranche(&mut self, tranche: Pubkey, ticket: &TestTicket, feed: Pubkey) -> Result<(), TransportError> {
+        let ix = Instruction {
+            program_id: program_id(),
+            accounts: accounts::CheckPriceTranche {
+                price_tranche: tranche,
+                ticket: ticket.ticket.pubkey(),
+                vestor: self.vestor.pubkey(),
+                price_feed: feed,
+                chainlink_program: self.chainlink_program,
+            }
+            .to_account_metas(None),
+            data: instruction::CheckPriceTranche {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+}
+
+
+// ---------------------------------------------------------------------------------------
+// initialize
+
+#[tokio::test]
+async fn initialize_moves_deposit_to_ticket_creator() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+
+    assert_eq!(env.token_balance(env.contract_owner_deposit.pubkey()).await, 0);
+    assert_eq!(env.token_balance(env.ticket_creator_deposit.pubkey()).await, DEPOSIT);
+
+    let vestor: vestor_using_anchor_chainlink_master::Vestor = env.account(env.vestor.pubkey()).await.unwrap();
+    assert_eq!(vestor.authority, env.payer());
+    assert_eq!(vestor.randomness_feed, env.randomness_feed.pubkey());
+    assert_eq!(vestor.oracles.chainlink_sol_feed, env.sol_feed);
+}
+
+#[tokio::test]
+async fn initialize_starts_on_the_devnet_oracles() {
+    let mut env = Env::new().await;
+    env.initialize_vestor().await.unwrap();
+
+    let vestor: vestor_using_anchor_chainlink_master::Vestor = env.account(env.vestor.pubkey()).await.unwrap();
+    let devnet = OracleRegistry::devnet();
+    assert_eq!(vestor.oracles.chainlink_program, devnet.chainlink_program);
+    assert_eq!(vestor.oracles.chainlink_sol_feed, devnet.chainlink_sol_feed);
+    assert_eq!(vestor.oracles.chainlink_eth_feed, devnet.chainlink_eth_feed);
+    assert_eq!(vestor.oracles.pyth_program, devnet.pyth_program);
+    assert_eq!(vestor.oracles.pyth_sol_price, devnet.pyth_sol_price);
+}
+
+#[tokio::test]
+async fn initialize_rejects_more_than_the_deposit() {
+    let mut env = Env::new().await;
+    env.create_mint().await;
+    let payer = env.payer();
+    let contract_owner_deposit = Keypair::from_bytes(&env.contract_owner_deposit.to_bytes()).unwrap();
+    let ticket_creator_deposit = Keypair::from_bytes(&env.ticket_creator_deposit.to_bytes()).unwrap();
+    env.create_token_account(&contract_owner_deposit, &payer).await;
+    env.create_token_account(&ticket_creator_deposit, &payer).await;
+
+    // Nothing was minted, so the SPL transfer fails with InsufficientFunds
+    let initialize = Instruction {
+        program_id: program_id(),
+        accounts: accounts::Initialize {
+            vestor: env.vestor.pubkey(),
+            contract_owner_deposit_token_vault: contract_owner_deposit.pubkey(),
+            ticket_creator_deposit_token_vault: ticket_creator_deposit.pubkey(),
+            token_mint: env.mint.pubkey(),
+            owner: payer,
+            token_program: spl_token::id(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: instruction::Initialize { amount: DEPOSIT }.data(),
+    };
+    let vestor = Keypair::from_bytes(&env.vestor.to_bytes()).unwrap();
+    assert_error(
+        env.send(&[initialize], &[&vestor]).await,
+        spl_token::error::TokenError::InsufficientFunds as u32,
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// create_ticket
+
+#[tokio::test]
+async fn create_ticket_funds_the_vault() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, AMOUNT);
+    assert_eq!(env.token_balance(env.ticket_creator_deposit.pubkey()).await, DEPOSIT - AMOUNT);
+    assert_eq!(env.tickets_issued().await, 1);
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.claimant, ticket.claimant.pubkey());
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(state.balance, AMOUNT);
+    assert_eq!(state.created_at, START as u64);
+    assert_eq!(state.signer_seed, 0);
+}
+
+#[tokio::test]
+async fn create_ticket_rejects_invalid_terms() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+
+    assert_error(
+        env.create_ticket(CLIFF_DAYS, VESTING_DAYS, 0, false).await.map(|_| ()),
+        u32::from(ErrorCode::AmountMustBeGreaterThanZero),
+    );
+    assert_error(
+        env.create_ticket(VESTING_DAYS, CLIFF_DAYS, AMOUNT, false).await.map(|_| ()),
+        u32::from(ErrorCode::VestingPeriodShouldBeEqualOrLongerThanCliff),
+    );
+    assert_error(
+        env.create_ticket(0, 0, AMOUNT, false).await.map(|_| ()),
+        u32::from(ErrorCode::VestingPeriodMustBeNonZero),
+    );
+    assert_error(
+        env.create_ticket(CLIFF_DAYS, VESTING_DAYS, DEPOSIT + 1, false).await.map(|_| ()),
+        u32::from(ErrorCode::NotEnoughTokens),
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// claim
+
+#[tokio::test]
+async fn claim_pays_out_the_vested_amount() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    // Tickets on either side, so the claimed ticket's signer seed no longer matches the count
+    env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    // Before the cliff nothing is vested
+    env.warp_days(CLIFF_DAYS - 1).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, 0);
+
+    // Half way through vesting
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.claimed, AMOUNT / 2);
+    assert_eq!(state.balance, AMOUNT / 2);
+    assert_eq!(state.num_claims, 2);
+
+    // After vesting the rest is paid and the empty vault goes back to the owner
+    env.warp_to(START + ((VESTING_DAYS + 1) * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT);
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, 0);
+    assert_eq!(env.token_owner(ticket.vault.pubkey()).await, env.payer());
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.claimed, AMOUNT);
+    assert_eq!(state.balance, 0);
+}
+
+#[tokio::test]
+async fn claim_stores_chainlink_values() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    env.claim(&ticket).await.unwrap();
+
+    let vestor = env.vestor.pubkey();
+    let (sol_value, _) = Pubkey::find_program_address(
+        &[CHAINLINK_VALUE_SEED, vestor.as_ref(), env.sol_feed.as_ref()],
+        &program_id(),
+    );
+    let value: vestor_using_anchor_chainlink_master::ChainlinkValue = env.account(sol_value).await.unwrap();
+    assert_eq!(value.value, SOL_PRICE);
+    assert_eq!(value.decimals, 8);
+    assert_eq!(value.updated_at, START as u64);
+}
+
+#[tokio::test]
+async fn claim_vests_on_schedule_with_stale_oracles() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    // Neither Chainlink nor Pyth has updated since creation, which only rules out an early release
+    env.request_early_release(&ticket).await.unwrap();
+    env.publish_randomness([7; 32]).await;
+    env.warp_days(VESTING_DAYS / 2).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn claim_rejects_unregistered_feed() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    let fake_feed = Pubkey::new_unique();
+    let data = MockFeed {
+        round: MockRound { round_id: 1, slot: 0, timestamp: START as u32, answer: SOL_PRICE * 100 },
+        decimals: 8,
+        description: "SOL / USD".to_string(),
+    }
+    .try_to_vec()
+    .unwrap();
+    let chainlink_program = env.chainlink_program;
+    env.set_account(fake_feed, chainlink_program, data);
+
+    let ix = env.claim_ix(&ticket, &ticket.claimant.pubkey(), fake_feed);
+    assert_error(env.send(&[ix], &[&ticket.claimant]).await, u32::from(ErrorCode::InvalidOracleFeed));
+}
+
+#[tokio::test]
+async fn claim_works_without_a_randomness_feed() {
+    let mut env = Env::new().await;
+    env.initialize_without_randomness().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn early_release_is_drawn_once() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    env.request_early_release(&ticket).await.unwrap();
+    env.publish_randomness([7; 32]).await;
+
+    // SOL is below ETH, so the draw cannot release anything; the claim before the cliff pays nothing
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, 0);
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.lottery_round, 1);
+    env.warp_days(1).await;
+    assert_error(
+        env.request_early_release(&ticket).await,
+        u32::from(ErrorCode::EarlyReleaseAlreadyRequested),
+    );
+}
+
+#[tokio::test]
+async fn claim_rejects_anyone_but_the_claimant() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    let impostor = Keypair::new();
+    env.fund(impostor.pubkey(), 1_000_000_000).await;
+
+    let ix = env.claim_ix(&ticket, &impostor.pubkey(), env.sol_feed);
+    assert_error(
+        env.send(&[ix], &[&impostor]).await,
+        anchor_lang::error::ErrorCode::ConstraintHasOne as u32,
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// revoke
+
+#[tokio::test]
+async fn revoke_returns_the_balance() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+
+    let ix = env.revoke_ix(&ticket, env.payer());
+    env.send(&[ix], &[]).await.unwrap();
+
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 4);
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, 0);
+    assert_eq!(
+        env.token_balance(env.ticket_creator_deposit.pubkey()).await,
+        DEPOSIT - AMOUNT / 4
+    );
+    // The ticket is closed into its creator
+    assert!(env.account::<Ticket>(ticket.ticket.pubkey()).await.is_none());
+}
+
+#[tokio::test]
+async fn revoke_rejects_irrevocable_ticket() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, true).await.unwrap();
+
+    let ix = env.revoke_ix(&ticket, env.payer());
+    assert_error(env.send(&[ix], &[]).await, u32::from(ErrorCode::TicketIrrevocable));
+}
+
+#[tokio::test]
+async fn revoke_rejects_anyone_but_the_owner() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    let impostor = Keypair::new();
+    let ix = env.revoke_ix(&ticket, impostor.pubkey());
+    assert_error(
+        env.send(&[ix], &[&impostor]).await,
+        anchor_lang::error::ErrorCode::ConstraintAddress as u32,
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// schedule amendments
+
+#[tokio::test]
+async fn amend_schedule_needs_owner_and_claimant() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    let mut owner_only = env.amend_schedule_ix(&ticket, ticket.claimant.pubkey(), 0, VESTING_DAYS / 2);
+    for meta in owner_only.accounts.iter_mut().filter(|m| m.pubkey == ticket.claimant.pubkey()) {
+        meta.is_signer = false;
+    }
+    assert_error(
+        env.send(&[owner_only], &[]).await,
+        anchor_lang::error::ErrorCode::AccountNotSigner as u32,
+    );
+
+    let impostor = Keypair::new();
+    let ix = env.amend_schedule_ix(&ticket, impostor.pubkey(), 0, VESTING_DAYS / 2);
+    assert_error(
+        env.send(&[ix], &[&impostor]).await,
+        anchor_lang::error::ErrorCode::ConstraintHasOne as u32,
+    );
+
+    let ix = env.amend_schedule_ix(&ticket, ticket.claimant.pubkey(), 0, VESTING_DAYS / 2);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!((state.cliff, state.vesting), (0, VESTING_DAYS / 2));
+}
+
+#[tokio::test]
+async fn amend_schedule_never_reduces_the_vested_amount() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    let claimant = ticket.claimant.pubkey();
+
+    // Half has vested; a longer schedule or a cliff still ahead would take some of it back
+    let longer = env.amend_schedule_ix(&ticket, claimant, 0, VESTING_DAYS * 2);
+    assert_error(env.send(&[longer], &[&ticket.claimant]).await, u32::from(ErrorCode::AmendmentReducesVestedAmount));
+    let later_cliff = env.amend_schedule_ix(&ticket, claimant, VESTING_DAYS * 3 / 4, VESTING_DAYS);
+    assert_error(env.send(&[later_cliff], &[&ticket.claimant]).await, u32::from(ErrorCode::AmendmentReducesVestedAmount));
+
+    // The same limits as `create_ticket`
+    let instant = env.amend_schedule_ix(&ticket, claimant, 0, 0);
+    assert_error(env.send(&[instant], &[&ticket.claimant]).await, u32::from(ErrorCode::VestingPeriodMustBeNonZero));
+    let cliff_past_vesting = env.amend_schedule_ix(&ticket, claimant, VESTING_DAYS, VESTING_DAYS / 2);
+    assert_error(
+        env.send(&[cliff_past_vesting], &[&ticket.claimant]).await,
+        u32::from(ErrorCode::VestingPeriodShouldBeEqualOrLongerThanCliff),
+    );
+
+    // Shortening the schedule only speeds vesting up
+    let shorter = env.amend_schedule_ix(&ticket, claimant, 0, VESTING_DAYS * 3 / 4);
+    env.send(&[shorter], &[&ticket.claimant]).await.unwrap();
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT * 2 / 3);
+}
+
+
+// ---------------------------------------------------------------------------------------
+// pause and resume
+
+#[tokio::test]
+async fn a_paused_ticket_vests_nothing_until_resumed() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let day = |days: u64| START + (days * SECONDS_PER_DAY) as i64;
+
+    assert_error(env.resume_ticket(&ticket).await, u32::from(ErrorCode::TicketNotPaused));
+
+    // Paused a quarter of the way in, a claim half way in only pays the first quarter
+    env.warp_to(day(VESTING_DAYS / 4)).await;
+    env.pause_ticket(&ticket).await.unwrap();
+    env.warp_to(day(VESTING_DAYS / 2)).await;
+    assert_error(env.pause_ticket(&ticket).await, u32::from(ErrorCode::TicketPaused));
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 4);
+
+    // Resumed after half the vesting period, the schedule ends that much later
+    env.warp_to(day(VESTING_DAYS * 3 / 4)).await;
+    env.resume_ticket(&ticket).await.unwrap();
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.total_paused, VESTING_DAYS / 2 * SECONDS_PER_DAY);
+
+    env.warp_to(day(VESTING_DAYS)).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+
+    env.warp_to(day(VESTING_DAYS * 3 / 2)).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT);
+}
+
+#[tokio::test]
+async fn pause_rejects_irrevocable_ticket() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, true).await.unwrap();
+
+    assert_error(env.pause_ticket(&ticket).await, u32::from(ErrorCode::TicketIrrevocable));
+}
+
+
+// ---------------------------------------------------------------------------------------
+// acceleration
+
+#[tokio::test]
+async fn change_of_control_accelerates_a_single_trigger_ticket() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    env.set_acceleration(&ticket, 5_000, false).await.unwrap();
+
+    assert_error(env.accelerate(&ticket, 2_500).await, u32::from(ErrorCode::AccelerationNotTriggered));
+
+    let ix = env.set_change_of_control_ix(env.payer(), true);
+    env.send(&[ix], &[]).await.unwrap();
+
+    // At most the granted basis points, each usable once
+    assert_error(env.accelerate(&ticket, 6_000).await, u32::from(ErrorCode::InvalidBasisPoints));
+    env.accelerate(&ticket, 5_000).await.unwrap();
+    env.warp_days(1).await;
+    assert_error(env.accelerate(&ticket, 1).await, u32::from(ErrorCode::InvalidBasisPoints));
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.accelerated, AMOUNT / 2);
+    assert_eq!(state.acceleration_bps, 0);
+
+    // Accelerated tokens can be claimed even before the cliff
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn double_trigger_acceleration_needs_termination_too() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    env.set_acceleration(&ticket, 10_000, true).await.unwrap();
+    let ix = env.set_change_of_control_ix(env.payer(), true);
+    env.send(&[ix], &[]).await.unwrap();
+
+    assert_error(env.accelerate(&ticket, 10_000).await, u32::from(ErrorCode::AccelerationNotTriggered));
+
+    let ix = env.terminate_ticket_ix(&ticket, env.payer());
+    env.send(&[ix], &[]).await.unwrap();
+    env.warp_days(1).await;
+    let ix = env.terminate_ticket_ix(&ticket, env.payer());
+    assert_error(env.send(&[ix], &[]).await, u32::from(ErrorCode::TicketTerminated));
+
+    env.accelerate(&ticket, 10_000).await.unwrap();
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert!(state.is_terminated);
+    assert_eq!(state.accelerated, AMOUNT);
+}
+
+#[tokio::test]
+async fn change_of_control_and_termination_need_their_own_signers() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let impostor = Keypair::new();
+
+    let ix = env.set_change_of_control_ix(impostor.pubkey(), true);
+    assert_error(
+        env.send(&[ix], &[&impostor]).await,
+        anchor_lang::error::ErrorCode::ConstraintHasOne as u32,
+    );
+
+    // The claimant cannot terminate their own ticket either
+    let ix = env.terminate_ticket_ix(&ticket, ticket.claimant.pubkey());
+    assert_error(
+        env.send(&[ix], &[&ticket.claimant]).await,
+        anchor_lang::error::ErrorCode::ConstraintHasOne as u32,
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// USD terms
+
+#[tokio::test]
+async fn set_usd_terms_needs_owner_and_claimant() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let token_feed = Pubkey::new_unique();
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.set_mint_price_feed(token_feed).await;
+
+    let mut owner_only = env.set_usd_terms_ix(&ticket, 1_000_000);
+    for meta in owner_only.accounts.iter_mut().filter(|m| m.pubkey == ticket.claimant.pubkey()) {
+        meta.is_signer = false;
+    }
+    assert_error(
+        env.send(&[owner_only], &[]).await,
+        anchor_lang::error::ErrorCode::AccountNotSigner as u32,
+    );
+
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.usd_amount, 1_000_000);
+    assert_eq!(state.usd_price_feed, token_feed);
+}
+
+#[tokio::test]
+async fn set_usd_terms_rejects_mint_without_a_price_feed() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    assert_error(env.send(&[ix], &[&ticket.claimant]).await, u32::from(ErrorCode::NoMintPriceFeed));
+}
+
+#[tokio::test]
+async fn usd_claim_converts_at_the_mint_price() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let mut ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let token_feed = Pubkey::new_unique();
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.set_mint_price_feed(token_feed).await;
+
+    // A $1 grant in a $2 token is worth half the vault
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+    ticket.usd_price_feed = token_feed;
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 4);
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.usd_paid, 500_000);
+    assert_eq!(state.usd_unpaid, 0);
+
+    // The ticket's feed cannot be swapped for another one, such as the SOL feed
+    ticket.usd_price_feed = env.sol_feed;
+    assert_error(env.claim(&ticket).await, u32::from(ErrorCode::InvalidOracleFeed));
+}
+
+#[tokio::test]
+async fn set_usd_terms_rejects_claimed_ticket() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.claim(&ticket).await.unwrap();
+
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    assert_error(env.send(&[ix], &[&ticket.claimant]).await, u32::from(ErrorCode::TicketAlreadyClaimed));
+}
+
+#[tokio::test]
+async fn settling_a_usd_grant_returns_the_leftover_to_the_owner() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let mut ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, true).await.unwrap();
+    let token_feed = Pubkey::new_unique();
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.set_mint_price_feed(token_feed).await;
+
+    // A $1 grant in a $2 token only ever needs half the vault, even on an irrevocable ticket
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+    ticket.usd_price_feed = token_feed;
+
+    env.warp_to(START + (VESTING_DAYS * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.claim(&ticket).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.usd_paid, 1_000_000);
+    assert_eq!(state.balance, 0);
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, AMOUNT / 2);
+    assert_eq!(env.token_owner(ticket.vault.pubkey()).await, env.payer());
+}
+
+#[tokio::test]
+async fn usd_tickets_take_no_bonus_unlocks() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let token_feed = Pubkey::new_unique();
+    env.set_chainlink_feed(token_feed, TOKEN_PRICE, "TOKEN / USD").await;
+    env.set_mint_price_feed(token_feed).await;
+
+    // Acceleration granted before the USD terms rules them out
+    env.set_acceleration(&ticket, 5000, false).await.unwrap();
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    assert_error(env.send(&[ix], &[&ticket.claimant]).await, u32::from(ErrorCode::BonusUnlockOnUsdTicket));
+
+    env.set_acceleration(&ticket, 0, false).await.unwrap();
+    let ix = env.set_usd_terms_ix(&ticket, 1_000_000);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+
+    // And once the ticket is USD-denominated, neither acceleration nor tranches can be added
+    env.warp_days(1).await;
+    assert_error(
+        env.set_acceleration(&ticket, 5000, false).await,
+        u32::from(ErrorCode::BonusUnlockOnUsdTicket),
+    );
+    let sol_feed = env.sol_feed;
+    assert_error(
+        env.add_price_tranche(&ticket, sol_feed, 200, 1000, 0, None).await.map(|_| ()),
+        u32::from(ErrorCode::BonusUnlockOnUsdTicket),
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// price tranches
+
+#[tokio::test]
+async fn init_price_history_rejects_zero_interval() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+
+    let sol_feed = env.sol_feed;
+    assert_error(
+        env.init_price_history(sol_feed, 0).await.map(|_| ()),
+        u32::from(ErrorCode::InvalidOracleConfig),
+    );
+}
+
+#[tokio::test]
+async fn twap_tranche_window_must_fit_the_price_history() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let sol_feed = env.sol_feed;
+    let history = env.init_price_history(sol_feed, 60).await.unwrap();
+
+    // 64 points at least a minute apart always span 63 minutes
+    env.add_price_tranche(&ticket, sol_feed, 150, 1_000, 63 * 60, Some(history)).await.unwrap();
+    assert_error(
+        env.add_price_tranche(&ticket, sol_feed, 150, 1_000, 63 * 60 + 1, Some(history)).await.map(|_| ()),
+        u32::from(ErrorCode::InsufficientPriceHistory),
+    );
+    assert_error(
+        env.add_price_tranche(&ticket, sol_feed, 150, 1_000, 60, None).await.map(|_| ()),
+        u32::from(ErrorCode::InsufficientPriceHistory),
+    );
+
+    // The history must be the one recording the tranche's feed
+    let eth_feed = env.eth_feed;
+    assert_error(
+        env.add_price_tranche(&ticket, eth_feed, 150, 1_000, 60, Some(history)).await.map(|_| ()),
+        u32::from(ErrorCode::InvalidOracleFeed),
+    );
+}
+
+
+// ---------------------------------------------------------------------------------------
+// shared tickets
+
+#[tokio::test]
+async fn shared_ticket_claimants_claim_their_share() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_shared_ticket(&[5_000, 3_000, 2_000], AMOUNT, false).await.unwrap();
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, AMOUNT);
+
+    let state: SharedTicket = env.account(ticket.shared_ticket.pubkey()).await.unwrap();
+    let allocations: Vec<u64> = state.beneficiaries.iter().map(|b| b.allocation).collect();
+    assert_eq!(allocations, vec![AMOUNT / 2, AMOUNT * 3 / 10, AMOUNT / 5]);
+
+    // Claimants claim independently, each on their own allocation
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+    let (claimant, token_account) = &ticket.claimants[0];
+    env.claim_shared(&ticket, claimant, token_account.pubkey()).await.unwrap();
+    assert_eq!(env.token_balance(token_account.pubkey()).await, AMOUNT / 2 / 4);
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    let (claimant, token_account) = &ticket.claimants[1];
+    env.claim_shared(&ticket, claimant, token_account.pubkey()).await.unwrap();
+    assert_eq!(env.token_balance(token_account.pubkey()).await, AMOUNT * 3 / 10 / 2);
+
+    // Once fully vested everyone ends up with exactly their allocation
+    env.warp_to(START + ((VESTING_DAYS + 1) * SECONDS_PER_DAY) as i64).await;
+    for ((claimant, token_account), allocation) in ticket.claimants.iter().zip(&allocations) {
+        env.claim_shared(&ticket, claimant, token_account.pubkey()).await.unwrap();
+        assert_eq!(env.token_balance(token_account.pubkey()).await, *allocation);
+    }
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn create_shared_ticket_rejects_invalid_shares() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+
+    assert_error(
+        env.create_shared_ticket(&[5_000, 4_000], AMOUNT, false).await.map(|_| ()),
+        u32::from(ErrorCode::InvalidShares),
+    );
+    assert_error(
+        env.create_shared_ticket(&[10_000, 0], AMOUNT, false).await.map(|_| ()),
+        u32::from(ErrorCode::InvalidShares),
+    );
+    assert_error(
+        env.create_shared_ticket(&[1_000; 11], AMOUNT, false).await.map(|_| ()),
+        u32::from(ErrorCode::TooManySharedClaimants),
+    );
+}
+
+#[tokio::test]
+async fn claim_shared_rejects_anyone_without_a_share() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_shared_ticket(&[5_000, 5_000], AMOUNT, false).await.unwrap();
+
+    let stranger = Keypair::new();
+    let stranger_account = Keypair::new();
+    env.create_token_account(&stranger_account, &stranger.pubkey()).await;
+
+    env.warp_days(VESTING_DAYS).await;
+    assert_error(
+        env.claim_shared(&ticket, &stranger, stranger_account.pubkey()).await,
+        u32::from(ErrorCode::NotASharedTicketClaimant),
+    );
+}
+
+#[tokio::test]
+async fn revoke_shared_returns_every_unvested_remainder() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_shared_ticket(&[6_000, 4_000], AMOUNT, false).await.unwrap();
+
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+    let (claimant, token_account) = &ticket.claimants[0];
+    env.claim_shared(&ticket, claimant, token_account.pubkey()).await.unwrap();
+
+    // A quarter of each share has vested; the other three quarters go back to the owner
+    env.revoke_shared(&ticket).await.unwrap();
+    assert_eq!(
+        env.token_balance(env.ticket_creator_deposit.pubkey()).await,
+        DEPOSIT - AMOUNT / 4
+    );
+
+    // Vesting stops at revocation, but what had vested can still be claimed
+    env.warp_to(START + ((VESTING_DAYS + 1) * SECONDS_PER_DAY) as i64).await;
+    for (claimant, token_account) in &ticket.claimants {
+        env.claim_shared(&ticket, claimant, token_account.pubkey()).await.unwrap();
+    }
+    assert_eq!(env.token_balance(ticket.claimants[0].1.pubkey()).await, AMOUNT * 6 / 10 / 4);
+    assert_eq!(env.token_balance(ticket.claimants[1].1.pubkey()).await, AMOUNT * 4 / 10 / 4);
+    assert_eq!(env.token_balance(ticket.vault.pubkey()).await, 0);
+
+    assert_error(env.revoke_shared(&ticket).await, u32::from(ErrorCode::TicketRevoked));
+}
+
+#[tokio::test]
+async fn revoke_shared_rejects_irrevocable_ticket() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_shared_ticket(&[5_000, 5_000], AMOUNT, true).await.unwrap();
+
+    assert_error(env.revoke_shared(&ticket).await, u32::from(ErrorCode::TicketIrrevocable));
+}
+
+
+// ---------------------------------------------------------------------------------------
+// merkle pools
+
+#[tokio::test]
+async fn first_pool_claim_creates_the_position() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let pool = env.create_merkle_pool(&[AMOUNT / 2, AMOUNT / 4, AMOUNT / 4], AMOUNT).await.unwrap();
+    assert!(env.account::<PoolPosition>(env.position(&pool, 1)).await.is_none());
+
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+    let (claimant, token_account) = &pool.claimants[1];
+    env.claim_from_pool(&pool, 1, claimant, token_account.pubkey()).await.unwrap();
+
+    let position: PoolPosition = env.account(env.position(&pool, 1)).await.unwrap();
+    assert_eq!(position.pool, pool.pool.pubkey());
+    assert_eq!(position.index, 1);
+    assert_eq!(position.claimant, claimant.pubkey());
+    assert_eq!(position.amount, AMOUNT / 4);
+    assert_eq!(position.claimed, AMOUNT / 4 / 4);
+    assert_eq!(env.token_balance(token_account.pubkey()).await, AMOUNT / 4 / 4);
+}
+
+#[tokio::test]
+async fn repeat_pool_claims_pay_what_has_vested_since() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let pool = env.create_merkle_pool(&[AMOUNT / 2, AMOUNT / 2], AMOUNT).await.unwrap();
+    let (claimant, token_account) = &pool.claimants[0];
+
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+    env.claim_from_pool(&pool, 0, claimant, token_account.pubkey()).await.unwrap();
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.claim_from_pool(&pool, 0, claimant, token_account.pubkey()).await.unwrap();
+    assert_eq!(env.token_balance(token_account.pubkey()).await, AMOUNT / 2 / 2);
+
+    // Past the end of vesting the last claim pays out the rest of the allocation
+    env.warp_to(START + ((VESTING_DAYS + 1) * SECONDS_PER_DAY) as i64).await;
+    env.claim_from_pool(&pool, 0, claimant, token_account.pubkey()).await.unwrap();
+    assert_eq!(env.token_balance(token_account.pubkey()).await, AMOUNT / 2);
+
+    let position: PoolPosition = env.account(env.position(&pool, 0)).await.unwrap();
+    assert_eq!(position.claimed, AMOUNT / 2);
+    let state: MerklePool = env.account(pool.pool.pubkey()).await.unwrap();
+    assert_eq!(state.claimed, AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn claim_from_pool_rejects_a_wrong_proof() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let pool = env.create_merkle_pool(&[AMOUNT / 2, AMOUNT / 4, AMOUNT / 4], AMOUNT).await.unwrap();
+    env.warp_days(VESTING_DAYS).await;
+
+    let (claimant, token_account) = &pool.claimants[0];
+    let proof = merkle::proof(&pool.leaves, 2);
+    assert_error(
+        env.claim_from_pool_with_proof(&pool, 0, claimant, token_account.pubkey(), proof).await,
+        u32::from(ErrorCode::InvalidMerkleProof),
+    );
+    assert_eq!(env.token_balance(token_account.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn claim_from_pool_rejects_another_claimants_index() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let pool = env.create_merkle_pool(&[AMOUNT / 2, AMOUNT / 2], AMOUNT).await.unwrap();
+    env.warp_to(START + (VESTING_DAYS / 4 * SECONDS_PER_DAY) as i64).await;
+
+    // Neither before nor after its owner has created the position
+    let (thief, thief_account) = &pool.claimants[1];
+    assert_error(
+        env.claim_from_pool(&pool, 0, thief, thief_account.pubkey()).await,
+        u32::from(ErrorCode::InvalidMerkleProof),
+    );
+
+    let (claimant, token_account) = &pool.claimants[0];
+    env.claim_from_pool(&pool, 0, claimant, token_account.pubkey()).await.unwrap();
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    assert_error(
+        env.claim_from_pool(&pool, 0, thief, thief_account.pubkey()).await,
+        u32::from(ErrorCode::InvalidMerkleProof),
+    );
+    assert_eq!(env.token_balance(thief_account.pubkey()).await, 0);
+    let position: PoolPosition = env.account(env.position(&pool, 0)).await.unwrap();
+    assert_eq!(position.claimant, claimant.pubkey());
+    assert_eq!(position.claimed, AMOUNT / 2 / 4);
+}
+
+#[tokio::test]
+async fn close_merkle_pool_returns_unclaimed_and_excess_after_the_grace_period() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    // 1000 base units more than the leaves add up to
+    let pool = env.create_merkle_pool(&[AMOUNT / 2, AMOUNT / 2], AMOUNT + 1_000).await.unwrap();
+
+    env.warp_to(START + ((VESTING_DAYS + 1) * SECONDS_PER_DAY) as i64).await;
+    let (claimant, token_account) = &pool.claimants[0];
+    env.claim_from_pool(&pool, 0, claimant, token_account.pubkey()).await.unwrap();
+
+    // Claimants still have the grace period after full vesting
+    let fully_vested_at = START + (VESTING_DAYS * SECONDS_PER_DAY) as i64;
+    env.warp_to(fully_vested_at + MERKLE_POOL_CLAWBACK_GRACE as i64 - 1).await;
+    assert_error(env.close_merkle_pool(&pool).await, u32::from(ErrorCode::MerklePoolStillClaimable));
+
+    env.warp_to(fully_vested_at + MERKLE_POOL_CLAWBACK_GRACE as i64).await;
+    env.close_merkle_pool(&pool).await.unwrap();
+
+    // The pool and its vault are both closed, the vault's tokens and rent back with the authority
+    assert_eq!(env.token_balance(env.ticket_creator_deposit.pubkey()).await, DEPOSIT - AMOUNT / 2);
+    assert!(env.account::<MerklePool>(pool.pool.pubkey()).await.is_none());
+    assert!(env.ctx.banks_client.get_account(pool.vault.pubkey()).await.unwrap().is_none());
+
+    // With the pool gone nothing more can be claimed from it
+    let (claimant, token_account) = &pool.claimants[1];
+    assert!(env.claim_from_pool(&pool, 1, claimant, token_account.pubkey()).await.is_err());
+}
+
+
+// ---------------------------------------------------------------------------------------
+// mock oracle. Run with `cargo test --features mock-oracle`.
+
+#[cfg(not(feature = "mock-oracle"))]
+#[tokio::test]
+async fn claim_rejects_a_program_owned_feed_outside_mock_builds() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+
+    // The vestor itself is owned by the program, like a `MockPriceFeed` would be
+    let vestor = env.vestor.pubkey();
+    env.register_sol_feed(vestor).await;
+    let ix = env.claim_ix(&ticket, &ticket.claimant.pubkey(), vestor);
+    assert_error(env.send(&[ix], &[&ticket.claimant]).await, u32::from(ErrorCode::InvalidOracleProgram));
+}
+
+#[cfg(feature = "mock-oracle")]
+#[tokio::test]
+async fn price_tranche_releases_on_a_mock_price() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(CLIFF_DAYS, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let feed = env.init_mock_price_feed().await.unwrap();
+    let tranche = env.add_price_tranche(&ticket, feed, 150, 2_500, 0, None).await.unwrap();
+
+    // $100 is below the strike
+    env.set_mock_price(feed, 10_000, -2).await;
+    env.check_price_tranche(tranche, &ticket, feed).await.unwrap();
+    let state: vestor_using_anchor_chainlink_master::PriceTranche = env.account(tranche).await.unwrap();
+    assert!(!state.is_released);
+
+    // $150.00000001 clears it
+    env.warp_days(1).await;
+    env.set_mock_price(feed, 15_000_000_001, -8).await;
+    env.check_price_tranche(tranche, &ticket, feed).await.unwrap();
+    let state: vestor_using_anchor_chainlink_master::PriceTranche = env.account(tranche).await.unwrap();
+    assert!(state.is_released);
+    let state: Ticket = env.account(ticket.ticket.pubkey()).await.unwrap();
+    assert_eq!(state.milestone_unlocked, AMOUNT / 4);
+
+    env.warp_days(1).await;
+    assert_error(
+        env.check_price_tranche(tranche, &ticket, feed).await,
+        u32::from(ErrorCode::TrancheAlreadyReleased),
+    );
+}
+
+#[cfg(feature = "mock-oracle")]
+#[tokio::test]
+async fn claim_reads_a_mock_sol_feed() {
+    let mut env = Env::new().await;
+    env.initialize().await.unwrap();
+    let ticket = env.create_ticket(0, VESTING_DAYS, AMOUNT, false).await.unwrap();
+    let feed = env.init_mock_price_feed().await.unwrap();
+    env.register_sol_feed(feed).await;
+    env.set_mock_price(feed, 12_345, -2).await;
+
+    env.warp_to(START + (VESTING_DAYS / 2 * SECONDS_PER_DAY) as i64).await;
+    env.publish_prices(SOL_PRICE, ETH_PRICE).await;
+    let ix = env.claim_ix(&ticket, &ticket.claimant.pubkey(), feed);
+    env.send(&[ix], &[&ticket.claimant]).await.unwrap();
+    assert_eq!(env.token_balance(ticket.claimant_vault.pubkey()).await, AMOUNT / 2);
+
+    let vestor = env.vestor.pubkey();
+    let (sol_value, _) =
+        Pubkey::find_program_address(&[CHAINLINK_VALUE_SEED, vestor.as_ref(), feed.as_ref()], &program_id());
+    let value: vestor_using_anchor_chainlink_master::ChainlinkValue = env.account(sol_value).await.unwrap();
+    assert_eq!(value.value, 12_345);
+    assert_eq!(value.decimals, 2);
+}
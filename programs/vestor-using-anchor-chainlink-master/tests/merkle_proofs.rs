@@ -0,0 +1,59 @@
+// Proofs built off-chain with `merkle::proof` must verify against `merkle::root` on-chain,
+// and must not verify for any other index, claimant or amount.
+use anchor_lang::prelude::Pubkey;
+use proptest::prelude::*;
+
+use vestor_using_anchor_chainlink_master::merkle;
+
+
+fn allocations(n: usize) -> Vec<(Pubkey, u64)> {
+    (0..n).map(|i| (Pubkey::new_unique(), 1_000 + i as u64)).collect()
+}
+
+fn leaves(allocations: &[(Pubkey, u64)]) -> Vec<[u8; 32]> {
+    allocations
+        .iter()
+        .enumerate()
+        .map(|(i, (claimant, amount))| merkle::leaf(i as u64, claimant, *amount))
+        .collect()
+}
+
+
+#[test]
+fn single_leaf_is_its_own_root() {
+    let allocations = allocations(1);
+    let leaves = leaves(&allocations);
+
+    assert_eq!(merkle::root(&leaves), leaves[0]);
+    assert!(merkle::proof(&leaves, 0).is_empty());
+    assert!(merkle::verify(&[], &leaves[0], leaves[0]));
+}
+
+
+proptest! {
+    #[test]
+    fn every_proof_verifies(n in 1..200usize) {
+        let allocations = allocations(n);
+        let leaves = leaves(&allocations);
+        let root = merkle::root(&leaves);
+
+        for (i, (claimant, amount)) in allocations.iter().enumerate() {
+            let proof = merkle::proof(&leaves, i);
+            prop_assert!(merkle::verify(&proof, &root, merkle::leaf(i as u64, claimant, *amount)));
+        }
+    }
+
+    #[test]
+    fn tampered_claims_do_not_verify(n in 2..200usize, i in 0..200usize, extra in 1..u64::MAX / 2) {
+        let i = i % n;
+        let allocations = allocations(n);
+        let leaves = leaves(&allocations);
+        let root = merkle::root(&leaves);
+        let proof = merkle::proof(&leaves, i);
+        let (claimant, amount) = allocations[i];
+
+        prop_assert!(!merkle::verify(&proof, &root, merkle::leaf(i as u64, &claimant, amount + extra)));
+        prop_assert!(!merkle::verify(&proof, &root, merkle::leaf(i as u64, &Pubkey::new_unique(), amount)));
+        prop_assert!(!merkle::verify(&proof, &root, merkle::leaf(((i + 1) % n) as u64, &claimant, amount)));
+    }
+}
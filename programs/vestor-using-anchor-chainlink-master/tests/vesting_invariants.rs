@@ -0,0 +1,276 @@
+// Property tests for the ticket ledger. Random sequences of claims, early releases, USD
+// claims, revocations, pauses, resumes and clock jumps are applied to a `Ticket` exactly as the program
+// applies them, and the ledger invariants are checked after every step.
+use proptest::prelude::*;
+
+use vestor_using_anchor_chainlink_master::schedule::{
+    split_by_shares, ScheduleError, SECONDS_PER_DAY, TOTAL_SHARE_BPS,
+};
+use vestor_using_anchor_chainlink_master::Ticket;
+
+
+const CREATED_AT: i64 = 1_650_000_000;
+
+
+#[derive(Clone, Debug)]
+enum Op {
+    Claim,
+    ReleaseEarly,
+    // USD claim at a price in micro-dollars per token base unit
+    ClaimUsd(u64),
+    Revoke,
+    Pause,
+    Resume,
+    Advance(i64),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => Just(Op::Claim),
+        1 => Just(Op::ReleaseEarly),
+        3 => (1..=1_000u64).prop_map(Op::ClaimUsd),
+        1 => Just(Op::Revoke),
+        2 => Just(Op::Pause),
+        2 => Just(Op::Resume),
+        4 => (0..=(400 * SECONDS_PER_DAY as i64)).prop_map(Op::Advance),
+    ]
+}
+
+// Up to ten non-zero shares adding up to TOTAL_SHARE_BPS
+fn shares() -> impl Strategy<Value = Vec<u16>> {
+    prop::collection::vec(1..=1_000u16, 1..=10).prop_map(|weights| {
+        let total: u32 = weights.iter().map(|&w| w as u32).sum();
+        let mut shares: Vec<u16> = weights
+            .iter()
+            .map(|&w| ((w as u32 * TOTAL_SHARE_BPS as u32 / total) as u16).max(1))
+            .collect();
+        let assigned: u16 = shares[..shares.len() - 1].iter().sum();
+        *shares.last_mut().unwrap() = TOTAL_SHARE_BPS - assigned;
+        shares
+    })
+}
+
+fn ticket(amount: u64, cliff: u64, vesting: u64, bonus: u64) -> Ticket {
+    Ticket {
+        amount,
+        balance: amount,
+        created_at: CREATED_AT as u64,
+        cliff,
+        vesting,
+        accelerated: bonus,
+        ..Default::default()
+    }
+}
+
+fn usd_ticket(amount: u64, cliff: u64, vesting: u64, bonus: u64, usd_amount: u64) -> Ticket {
+    Ticket { usd_amount, ..ticket(amount, cliff, vesting, bonus) }
+}
+
+
+// What actually moved in and out of the vault, tracked independently of the ticket
+#[derive(Default)]
+struct Ledger {
+    granted: u64,
+    paid: u64,
+    returned: u64,
+    usd_paid: u64,
+}
+
+fn check(ticket: &Ticket, ledger: &Ledger) -> Result<(), TestCaseError> {
+    prop_assert_eq!(ticket.amount, ledger.granted);
+    prop_assert_eq!(ticket.claimed, ledger.paid);
+    prop_assert!(ticket.claimed <= ticket.amount);
+    prop_assert!(ledger.paid + ledger.returned <= ledger.granted);
+    prop_assert_eq!(ticket.usd_paid, ledger.usd_paid);
+    prop_assert!(ticket.usd_paid <= ticket.usd_amount);
+
+    // Revoking a ticket or settling its USD grant hands the rest of the balance back
+    if ticket.is_revoked || ticket.schedule().is_usd_settled() {
+        prop_assert_eq!(ticket.balance, 0);
+        prop_assert_eq!(ledger.paid + ledger.returned, ledger.granted);
+    } else {
+        prop_assert_eq!(ticket.claimed + ticket.balance, ticket.amount);
+        prop_assert_eq!(ledger.returned, 0);
+    }
+
+    Ok(())
+}
+
+
+proptest! {
+    #[test]
+    fn ledger_invariants_hold(
+        amount in 1..=1_000_000_000_000u64,
+        cliff in 0..=365u64,
+        vesting in 1..=1_460u64,
+        bonus in 0..=1_000_000_000u64,
+        usd_amount in 0..=1_000_000_000_000u64,
+        ops in prop::collection::vec(op(), 1..64),
+    ) {
+        let mut ticket = usd_ticket(amount, cliff, vesting, bonus, usd_amount);
+        let mut ledger = Ledger { granted: amount, ..Default::default() };
+        let mut now = CREATED_AT;
+        let mut last_vested = 0;
+        let mut vested_at_pause = 0;
+
+        for op in ops {
+            match op {
+                Op::Claim => {
+                    let mut schedule = ticket.schedule();
+                    match schedule.claim(now) {
+                        Ok(paid) => {
+                            ticket.set_ledger(&schedule);
+                            ledger.paid += paid;
+                        }
+                        Err(e) => {
+                            prop_assert!(ticket.is_revoked);
+                            prop_assert_eq!(e, ScheduleError::Revoked);
+                        }
+                    }
+                }
+                Op::ReleaseEarly => {
+                    let mut schedule = ticket.schedule();
+                    match schedule.release_early() {
+                        Ok(paid) => {
+                            prop_assert_eq!(paid, ticket.balance);
+                            ticket.set_ledger(&schedule);
+                            ledger.paid += paid;
+                        }
+                        Err(e) => prop_assert_eq!(e, ScheduleError::Revoked),
+                    }
+                }
+                Op::ClaimUsd(price) => {
+                    let mut schedule = ticket.schedule();
+                    let usd_due = schedule.usd_available(now).unwrap();
+                    let payout = schedule.claim_usd(
+                        now,
+                        ticket.balance,
+                        |usd| Ok::<_, ScheduleError>(usd / price),
+                        |tokens| tokens.checked_mul(price).ok_or(ScheduleError::Overflow),
+                    );
+                    match payout {
+                        Ok(payout) => {
+                            prop_assert!(payout.tokens <= ticket.balance);
+                            prop_assert!(payout.usd <= usd_due);
+                            prop_assert_eq!(schedule.usd_unpaid, usd_due - payout.usd);
+                            if payout.returned > 0 {
+                                prop_assert!(schedule.is_usd_settled());
+                            }
+                            ticket.set_ledger(&schedule);
+                            ledger.paid += payout.tokens;
+                            ledger.returned += payout.returned;
+                            ledger.usd_paid += payout.usd;
+                        }
+                        Err(e) => prop_assert_eq!(e, ScheduleError::Revoked),
+                    }
+                }
+                Op::Revoke => {
+                    let mut schedule = ticket.schedule();
+                    match schedule.revoke() {
+                        Ok(returned) => {
+                            ticket.set_ledger(&schedule);
+                            ledger.returned += returned;
+                        }
+                        Err(e) => prop_assert_eq!(e, ScheduleError::Revoked),
+                    }
+                }
+                Op::Pause => {
+                    let mut schedule = ticket.schedule();
+                    match schedule.pause(now) {
+                        Ok(()) => {
+                            ticket.set_ledger(&schedule);
+                            vested_at_pause = ticket.schedule().vested(now).unwrap();
+                        }
+                        Err(e) => prop_assert!(
+                            (ticket.is_revoked && e == ScheduleError::Revoked)
+                                || (ticket.is_paused && e == ScheduleError::Paused)
+                        ),
+                    }
+                }
+                Op::Resume => {
+                    let mut schedule = ticket.schedule();
+                    match schedule.resume(now) {
+                        Ok(paused_for) => {
+                            prop_assert_eq!(paused_for, now - ticket.paused_at as i64);
+                            ticket.set_ledger(&schedule);
+                            // Vesting picks up exactly where the pause left it
+                            prop_assert_eq!(ticket.schedule().vested(now).unwrap(), vested_at_pause);
+                        }
+                        Err(e) => prop_assert!(
+                            (ticket.is_revoked && e == ScheduleError::Revoked)
+                                || (!ticket.is_paused && e == ScheduleError::NotPaused)
+                        ),
+                    }
+                }
+                Op::Advance(seconds) => now += seconds,
+            }
+
+            check(&ticket, &ledger)?;
+
+            // Vesting never goes backwards
+            let vested = ticket.schedule().vested(now).unwrap();
+            prop_assert!(vested >= last_vested);
+            if ticket.is_paused {
+                prop_assert_eq!(vested, vested_at_pause);
+            }
+            prop_assert!(vested <= ticket.amount);
+            last_vested = vested;
+        }
+    }
+
+    #[test]
+    fn fully_vested_ticket_pays_out_exactly_amount(
+        amount in 1..=u64::MAX / 2,
+        cliff in 0..=365u64,
+        vesting in 1..=1_460u64,
+        claims in prop::collection::vec(0..=(200 * SECONDS_PER_DAY as i64), 0..16),
+    ) {
+        let mut ticket = ticket(amount, cliff, vesting, 0);
+        let mut now = CREATED_AT;
+        let mut paid = 0u64;
+
+        for step in claims {
+            now += step;
+            let mut schedule = ticket.schedule();
+            paid += schedule.claim(now).unwrap();
+            ticket.set_ledger(&schedule);
+        }
+
+        // Past both the cliff and the vesting period, whatever is left comes out in one claim
+        now += ((cliff + vesting) * SECONDS_PER_DAY) as i64 + 1;
+        let mut schedule = ticket.schedule();
+        paid += schedule.claim(now).unwrap();
+        ticket.set_ledger(&schedule);
+
+        prop_assert_eq!(paid, amount);
+        prop_assert_eq!(ticket.balance, 0);
+        prop_assert_eq!(ticket.schedule().available(now).unwrap(), 0);
+    }
+
+    #[test]
+    fn shares_split_the_whole_amount(amount in 0..=u64::MAX, shares in shares()) {
+        let allocations = split_by_shares(amount, &shares).unwrap();
+
+        prop_assert_eq!(allocations.len(), shares.len());
+        prop_assert_eq!(allocations.iter().map(|&a| a as u128).sum::<u128>(), amount as u128);
+
+        // Everyone but the last claimant gets exactly their pro-rata share, rounded down
+        for (allocation, bps) in allocations.iter().zip(&shares).take(shares.len() - 1) {
+            prop_assert_eq!(*allocation as u128, amount as u128 * *bps as u128 / TOTAL_SHARE_BPS as u128);
+        }
+    }
+
+    #[test]
+    fn shares_must_total_exactly_ten_thousand_bps(shares in prop::collection::vec(0..=TOTAL_SHARE_BPS, 0..=10)) {
+        let total: u32 = shares.iter().map(|&s| s as u32).sum();
+        let valid = !shares.is_empty() && !shares.contains(&0) && total == TOTAL_SHARE_BPS as u32;
+
+        match split_by_shares(1_000_000, &shares) {
+            Ok(_) => prop_assert!(valid),
+            Err(e) => {
+                prop_assert!(!valid);
+                prop_assert_eq!(e, ScheduleError::InvalidShares);
+            }
+        }
+    }
+}
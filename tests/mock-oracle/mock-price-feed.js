@@ -0,0 +1,89 @@
+const anchor = require('@project-serum/anchor');
+const assert = require("assert");
+const { SystemProgram } = require("@solana/web3.js");
+
+const TokenInstructions = require("@project-serum/serum").TokenInstructions;
+const serumCmn = require("@project-serum/common");
+
+// These tests need no devnet feeds, but the program must be built with the mock oracle,
+// so they live outside tests/ and are skipped by a plain `anchor test`:
+//   anchor build -- --features mock-oracle
+//   anchor run test-mock-oracle
+describe("mock-price-feed", () => {
+  const provider = anchor.Provider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.VestorUsingAnchorChainlinkMaster;
+
+  let vestor = anchor.web3.Keypair.generate();
+  let mockPriceFeed = anchor.web3.Keypair.generate();
+
+  it("Initializes a vestor", async () => {
+    const [mint, contractOwnerDepositTokenVault] = await serumCmn.createMintAndVault(
+      program.provider,
+      new anchor.BN(1000000)
+    );
+    const ticketCreatorDepositTokenVault = await serumCmn.createTokenAccount(
+      program.provider,
+      mint,
+      program.provider.wallet.publicKey
+    );
+
+    await program.rpc.initialize(new anchor.BN(10000), {
+      accounts: {
+        vestor: vestor.publicKey,
+        contractOwnerDepositTokenVault: contractOwnerDepositTokenVault,
+        owner: provider.wallet.publicKey,
+        tokenMint: mint,
+        ticketCreatorDepositTokenVault: ticketCreatorDepositTokenVault,
+        tokenProgram: TokenInstructions.TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [vestor],
+    });
+  });
+
+  it("Creates a mock price feed", async () => {
+    await program.rpc.initMockPriceFeed({
+      accounts: {
+        mockPriceFeed: mockPriceFeed.publicKey,
+        vestor: vestor.publicKey,
+        authority: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [mockPriceFeed],
+    });
+
+    const feed = await program.account.mockPriceFeed.fetch(mockPriceFeed.publicKey);
+    assert.ok(feed.vestor.equals(vestor.publicKey));
+  });
+
+  it("Lets the vestor authority set the mock price", async () => {
+    // $150.00000000 with Chainlink-style 8 decimals
+    await program.rpc.setMockPrice(new anchor.BN("15000000000"), -8, {
+      accounts: {
+        mockPriceFeed: mockPriceFeed.publicKey,
+        vestor: vestor.publicKey,
+        authority: provider.wallet.publicKey,
+      },
+    });
+
+    const feed = await program.account.mockPriceFeed.fetch(mockPriceFeed.publicKey);
+    assert.equal(feed.value.toString(), "15000000000");
+    assert.equal(feed.expo, -8);
+  });
+
+  it("Rejects mock prices from anyone else", async () => {
+    const impostor = anchor.web3.Keypair.generate();
+    await assert.rejects(
+      program.rpc.setMockPrice(new anchor.BN(1), 0, {
+        accounts: {
+          mockPriceFeed: mockPriceFeed.publicKey,
+          vestor: vestor.publicKey,
+          authority: impostor.publicKey,
+        },
+        signers: [impostor],
+      })
+    );
+  });
+});
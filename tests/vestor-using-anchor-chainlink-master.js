@@ -23,6 +23,7 @@ const DIVISOR = 100000000;
 // THis is the link that contains all the devnet accounts https://pyth.network/developers/accounts/?cluster=devnet#
 let PYTH_SOL_PRICE_ACCOUNT = new anchor.web3.PublicKey("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix")
 let PYTH_SOL_SYMBOL_ACCOUNT = new anchor.web3.PublicKey("3Mnn2fX6rQyUsyELYms1sBJyChWofzSNRoqYzvgMVz5E");
+const PYTH_PROGRAM_ID = new anchor.web3.PublicKey("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
 
 
 
@@ -43,6 +44,8 @@ describe("vestor-using-anchor-chainlink-master", () => {
   let vestor = anchor.web3.Keypair.generate();
   let ticket = anchor.web3.Keypair.generate();
   let ticketSigner = null;
+  // Local stand-in for a VRF oracle: the provider wallet publishes the randomness itself.
+  let randomnessFeed = anchor.web3.Keypair.generate();
 
   it("Sets up initial test state", async () => {
     const [_mint, _contractOwnerDepositTokenVault] = await serumCmn.createMintAndVault(
@@ -107,6 +110,25 @@ describe("vestor-using-anchor-chainlink-master", () => {
 
   });
 
+  it("Registers the devnet oracles", async () => {
+    await program.rpc.setOracleRegistry({
+      chainlinkProgram: CHAINLINK_PROGRAM_ID,
+      chainlinkSolFeed: CHAINLINK_SOLANA_FEED,
+      chainlinkEthFeed: CHAINLINK_ETHEREUM_FEED,
+      pythProgram: PYTH_PROGRAM_ID,
+      pythSolPrice: PYTH_SOL_PRICE_ACCOUNT,
+    }, {
+      accounts: {
+        vestor: vestor.publicKey,
+        authority: provider.wallet.publicKey,
+      },
+    });
+
+    const vestorAccount = await program.account.vestor.fetch(vestor.publicKey);
+    assert.ok(vestorAccount.oracles.chainlinkSolFeed.equals(CHAINLINK_SOLANA_FEED));
+    assert.ok(vestorAccount.oracles.pythSolPrice.equals(PYTH_SOL_PRICE_ACCOUNT));
+  });
+
   it("Creates Tickets", async () => {
 
     const vestorAccount = await program.account.vestor.fetch(vestor.publicKey);
@@ -143,7 +165,7 @@ describe("vestor-using-anchor-chainlink-master", () => {
       },
       signers: [ticket, vault],
       instructions: [
-        await program.account.ticket.createInstruction(ticket, 300),
+        await program.account.ticket.createInstruction(ticket, 500),
         ...(await serumCmn.createTokenAccountInstrs(
           provider,
           vault.publicKey,
@@ -160,6 +182,68 @@ describe("vestor-using-anchor-chainlink-master", () => {
 
   });
 
+  it("Initializes a mock randomness feed", async () => {
+    await program.rpc.initRandomnessFeed({
+      accounts: {
+        randomnessFeed: randomnessFeed.publicKey,
+        vestor: vestor.publicKey,
+        randomnessAuthority: provider.wallet.publicKey,
+        authority: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [randomnessFeed],
+    });
+
+    const feedAccount = await program.account.randomnessFeed.fetch(randomnessFeed.publicKey);
+    assert.ok(feedAccount.authority.equals(provider.wallet.publicKey));
+    assert.equal(feedAccount.round.toNumber(), 0);
+
+    const vestorAccount = await program.account.vestor.fetch(vestor.publicKey);
+    assert.ok(vestorAccount.randomnessFeed.equals(randomnessFeed.publicKey));
+  });
+
+  it("Commits a ticket to the next randomness round", async () => {
+    await program.rpc.requestEarlyRelease({
+      accounts: {
+        ticket: ticket.publicKey,
+        vestor: vestor.publicKey,
+        randomnessFeed: randomnessFeed.publicKey,
+        claimant: claimant.publicKey,
+      },
+      signers: [claimant],
+    });
+
+    const ticketAccount = await program.account.ticket.fetch(ticket.publicKey);
+    assert.equal(ticketAccount.lotteryRound.toNumber(), 1);
+  });
+
+  it("Publishes mock randomness for the committed round", async () => {
+    await program.rpc.publishRandomness(Array(32).fill(7), {
+      accounts: {
+        randomnessFeed: randomnessFeed.publicKey,
+        randomnessAuthority: provider.wallet.publicKey,
+      },
+    });
+
+    const feedAccount = await program.account.randomnessFeed.fetch(randomnessFeed.publicKey);
+    assert.equal(feedAccount.round.toNumber(), 1);
+    assert.deepEqual(feedAccount.value, Array(32).fill(7));
+  });
+
+  it("Rejects randomness from anyone but the feed authority", async () => {
+    const impostor = anchor.web3.Keypair.generate();
+    await assert.rejects(
+      program.rpc.publishRandomness(Array(32).fill(1), {
+        accounts: {
+          randomnessFeed: randomnessFeed.publicKey,
+          randomnessAuthority: impostor.publicKey,
+        },
+        signers: [impostor],
+      }),
+      (err) => err.msg === "Signer is not the randomness authority"
+    );
+  });
+
 
 });
 